@@ -1,38 +1,173 @@
 
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, system_program};
 use anchor_spl::{
-    associated_token::AssociatedToken,
-    token::{Mint, Token, TokenAccount, transfer, Transfer, mint_to, MintTo, burn, Burn}
+    associated_token::{self, AssociatedToken},
+    token::{self, Mint, Token, TokenAccount, transfer, Transfer, mint_to, MintTo, burn, Burn, close_account, CloseAccount},
+    token_2022::{self, spl_token_2022::{extension::ExtensionType, instruction::initialize_non_transferable_mint}},
+    token_interface::{self, TokenInterface},
 };
+use math::*;
+use oracle::*;
+use orderbook::*;
+use pricing::*;
+
+mod math;
+mod oracle;
+mod orderbook;
+mod pricing;
 
 declare_id!("BfrkttNPsNutRR3PKtsh8N2cN3EhkqXJWRwG5RSMU8AK");
 /* 
     definitions:
-    principal token : token used to buy options
+    quote token : token used to buy options, pay strikes and collateralize puts.
+                  native SOL is quoted through the wrapped SOL mint.
     option token : token representing an option
     underlying token : token that the option is for.
 
-    pairing of option token : principal token
+    pairing of option token : quote token
     option token is tradeable for underlying token
 */
 const OPTION_MINT_DECIMALS: u8 = 6;
+// option base units per contract, prices are quoted per contract
+const PRICE_SCALE: u64 = 10u64.pow(OPTION_MINT_DECIMALS as u32);
+// european and bermudan options are exercisable for this long up to each exercise date
+const EXERCISE_WINDOW_SECONDS: u64 = 60 * 60;
+const MAX_EXERCISE_DATES: usize = 8;
 const POOL_FEE_BASIS_POINTS: u64 = 1;
 const LIST_FEE_BASIS_POINTS: u64 = 2;
+// part of each pool fee paid to the treasury, the rest stays in the pool for lps
+const PROTOCOL_FEE_SHARE_BASIS_POINTS: u64 = 5000;
+// cumulative price snapshots each pool keeps for twap reads, at least
+// `OBSERVATION_INTERVAL_SECONDS` apart so they cover eight hours or more of history
+const POOL_OBSERVATIONS: usize = 96;
+const OBSERVATION_INTERVAL_SECONDS: u64 = 5 * 60;
 #[program]
 pub mod options {
     use super::*;
-    pub fn initialize(_ctx: Context<Initialize>) -> Result<()> {
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        ctx.accounts.treasury.admin = ctx.accounts.signer.key();
+        Ok(())
+    }
+    // pays collected fees of one quote mint out of the treasury
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>, amount: u64) -> Result<()> {
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.treasury_vault.to_account_info(),
+                    to: ctx.accounts.admin_quote_token_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            amount,
+        )?;
+        Ok(())
+    }
+    // mock feeds are created by the treasury admin and only read by `mock-oracle` builds
+    pub fn init_mock_oracle(ctx: Context<InitMockOracle>, price: i64, conf: u64, expo: i32) -> Result<()> {
+        ctx.accounts.mock_oracle.authority = ctx.accounts.signer.key();
+        ctx.accounts.mock_oracle.price = price;
+        ctx.accounts.mock_oracle.conf = conf;
+        ctx.accounts.mock_oracle.expo = expo;
+        ctx.accounts.mock_oracle.publish_time = Clock::get()?.unix_timestamp;
         Ok(())
     }
-    pub fn create_holder_account(_ctx: Context<CreateHolderAccount>) -> Result<()> {
+    pub fn set_mock_price(ctx: Context<SetMockPrice>, price: i64, conf: u64) -> Result<()> {
+        ctx.accounts.mock_oracle.price = price;
+        ctx.accounts.mock_oracle.conf = conf;
+        ctx.accounts.mock_oracle.publish_time = Clock::get()?.unix_timestamp;
         Ok(())
     }
     // should initialize an option token, set its data, and mint it to the user
     // if option token already exists, should mint it to the user
     // should take underlying token from the user and hold as collateral. 
-    pub fn create(ctx: Context<Create>, end_time: u64, strike_price: u64, amount: u64, call: bool, resellable: bool) -> Result<()> {
+    // the option mint is derived from the series terms, so writers with identical terms share a mint
+    // cash settled series pay intrinsic value off `oracle`, physically settled series pass the default pubkey.
+    // cash settled calls are collateralized in underlying and pay the intrinsic value in underlying at the spot
+    #[allow(clippy::too_many_arguments)]
+    pub fn create(ctx: Context<Create>, end_time: u64, strike_price: u64, contract_multiplier: u64, amount: u64, call: bool, resellable: bool, cash_settled: bool, exercise_style: ExerciseStyle) -> Result<()> {
+        let time = Clock::get()?.unix_timestamp as u64;
+        if time > end_time {
+            return Err(CustomError::OptionExpired.into())
+        }
+        if contract_multiplier == 0 {
+            return Err(CustomError::InvalidContractMultiplier.into())
+        }
+        // empty positions would sit in the assignment rotation without ever being assigned
+        if amount == 0 {
+            return Err(CustomError::InvalidWriteAmount.into())
+        }
+        if cash_settled == (ctx.accounts.oracle.key() == Pubkey::default()) {
+            return Err(CustomError::InvalidAccount.into())
+        }
+        if cash_settled {
+            // only supported feed layouts can settle a series
+            decode_price(&ctx.accounts.oracle)?;
+        }
+        if let ExerciseStyle::Bermudan { dates } = &exercise_style {
+            let increasing = dates.windows(2).all(|pair| pair[0] < pair[1]);
+            if dates.is_empty() || dates.len() > MAX_EXERCISE_DATES || !increasing || dates[dates.len() - 1] > end_time {
+                return Err(CustomError::InvalidExerciseDates.into())
+            }
+        }
+        if ctx.accounts.option_mint.data_is_empty() {
+            let underlying_key = ctx.accounts.underlying_mint.key();
+            let quote_key = ctx.accounts.quote_mint.key();
+            let oracle_key = ctx.accounts.oracle.key();
+            let strike_bytes = strike_price.to_be_bytes();
+            let multiplier_bytes = contract_multiplier.to_be_bytes();
+            let end_time_bytes = end_time.to_be_bytes();
+            let style_seed = exercise_style.seed();
+            let seeds: &[&[u8]] = &[
+                b"option_mint",
+                underlying_key.as_ref(),
+                quote_key.as_ref(),
+                &strike_bytes,
+                &multiplier_bytes,
+                &end_time_bytes,
+                &[call as u8],
+                &[resellable as u8],
+                &[cash_settled as u8],
+                oracle_key.as_ref(),
+                &style_seed,
+                &[ctx.bumps.option_mint],
+            ];
+            create_option_mint(ctx.accounts, seeds, resellable)?;
+        }
+        associated_token::create_idempotent(
+            CpiContext::new(
+                ctx.accounts.associated_token_program.to_account_info(),
+                associated_token::Create {
+                    payer: ctx.accounts.signer.to_account_info(),
+                    associated_token: ctx.accounts.user_option_token_account.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info(),
+                    mint: ctx.accounts.option_mint.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.option_token_program.to_account_info(),
+                }
+            ),
+        )?;
+        if ctx.accounts.option_data_account.creator == Pubkey::default() {
+            // first write into this series
+            ctx.accounts.option_data_account.end_time = end_time;
+            ctx.accounts.option_data_account.strike_price = strike_price;
+            ctx.accounts.option_data_account.contract_multiplier = contract_multiplier;
+            ctx.accounts.option_data_account.price_scale = PRICE_SCALE;
+            ctx.accounts.option_data_account.call = call;
+            ctx.accounts.option_data_account.resellable = resellable;
+            ctx.accounts.option_data_account.creator = ctx.accounts.signer.key();
+            ctx.accounts.option_data_account.underlying_mint = ctx.accounts.underlying_mint.key();
+            ctx.accounts.option_data_account.quote_mint = ctx.accounts.quote_mint.key();
+            ctx.accounts.option_data_account.underlying_decimals = ctx.accounts.underlying_mint.decimals;
+            ctx.accounts.option_data_account.quote_decimals = ctx.accounts.quote_mint.decimals;
+            ctx.accounts.option_data_account.cash_settled = cash_settled;
+            ctx.accounts.option_data_account.oracle = ctx.accounts.oracle.key();
+            ctx.accounts.option_data_account.exercise_style = exercise_style;
+        }
+        let collateral = collateral_for_options(&ctx.accounts.option_data_account, amount, Rounding::Up)?;
         // transfer underlying from user to token account
-        if call {
+        if ctx.accounts.option_data_account.collateral_in_underlying() {
             // option is a call, user can buy token at strike_price
             transfer(
                 CpiContext::new(
@@ -43,26 +178,28 @@ pub mod options {
                         authority: ctx.accounts.signer.to_account_info(),
                     }
                 ),
-                amount,
+                collateral,
             )?;
         } else {
             // option is a put, user can sell token for strike_price, 
-            anchor_lang::system_program::transfer(
+            // or a cash settled put backed by the strike
+            transfer(
                 CpiContext::new(
-                    ctx.accounts.system_program.to_account_info(),
-                    anchor_lang::system_program::Transfer {
-                        from: ctx.accounts.signer.to_account_info(),
-                        to: ctx.accounts.program_authority.to_account_info(),
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.user_quote_token_account.to_account_info(),
+                        to: ctx.accounts.quote_token_account.to_account_info(),
+                        authority: ctx.accounts.signer.to_account_info(),
                     }
                 ),
-                strike_price * amount,
+                collateral,
             )?;
         }
         // mint option token to user
-        mint_to(
+        token_interface::mint_to(
             CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                MintTo {
+                ctx.accounts.option_token_program.to_account_info(),
+                token_interface::MintTo {
                     mint: ctx.accounts.option_mint.to_account_info(),
                     to: ctx.accounts.user_option_token_account.to_account_info(),
                     authority: ctx.accounts.program_authority.to_account_info(),
@@ -71,121 +208,329 @@ pub mod options {
             ),
             amount,
         )?;
-        ctx.accounts.option_data_account.end_time = end_time;
-        ctx.accounts.option_data_account.strike_price = strike_price;
-        ctx.accounts.option_data_account.amount_unexercised = amount;
-        ctx.accounts.option_data_account.call = call;
-        ctx.accounts.option_data_account.resellable = resellable;
-        ctx.accounts.option_data_account.creator = ctx.accounts.signer.key();
-        ctx.accounts.option_data_account.underlying_mint = ctx.accounts.underlying_mint.key();
+        ctx.accounts.option_data_account.amount_unexercised = checked_add(ctx.accounts.option_data_account.amount_unexercised, amount)?;
+        ctx.accounts.option_data_account.amount_written = checked_add(ctx.accounts.option_data_account.amount_written, amount)?;
+        let position = &mut ctx.accounts.writer_position;
+        if position.writer == Pubkey::default() {
+            // new writer joins the back of the assignment rotation
+            position.series = ctx.accounts.option_data_account.key();
+            position.writer = ctx.accounts.signer.key();
+            position.index = ctx.accounts.option_data_account.writer_count;
+            ctx.accounts.option_data_account.writer_count += 1;
+        }
+        position.collateral_deposited = checked_add(position.collateral_deposited, collateral)?;
+        position.contracts_written = checked_add(position.contracts_written, amount)?;
         Ok(())
     }
-    pub fn create_pool(ctx: Context<CreatePool>, base_price: u64, amount: u64) -> Result<()> {
+    // pools quote option tokens against a constant product curve, or off `oracle` with a black-scholes model.
+    // model priced pools are only opened on cash settled series, against the series' oracle.
+    // the constant product quote side starts with a virtual reserve pricing the deposit at `base_price`
+    // per contract, only quote actually paid in is held by the pool and can be paid out
+    pub fn create_pool(ctx: Context<CreatePool>, base_price: u64, amount: u64, curve: PoolCurve) -> Result<()> {
+        let virtual_quote_reserve = quote_for_options(amount, base_price, PRICE_SCALE, Rounding::Down)?;
+        if virtual_quote_reserve == 0 {
+            return Err(CustomError::PoolEmpty.into())
+        }
+        if let PoolCurve::BlackScholes { volatility_bps, .. } = curve {
+            if volatility_bps == 0 || volatility_bps > MAX_VOLATILITY_BPS {
+                return Err(CustomError::InvalidPoolCurve.into())
+            }
+            // model priced pools read the series' own settlement feed, so only cash settled series
+            // have one bound to their underlying and quote
+            if !ctx.accounts.option_data_account.cash_settled {
+                return Err(CustomError::InvalidPoolCurve.into())
+            }
+            if ctx.accounts.oracle.key() != ctx.accounts.option_data_account.oracle {
+                return Err(CustomError::InvalidAccount.into())
+            }
+            decode_price(&ctx.accounts.oracle)?;
+        }
         transfer(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 Transfer {
                     from: ctx.accounts.user_option_account.to_account_info(),
-                    to: ctx.accounts.program_holder_account.to_account_info(),
+                    to: ctx.accounts.pool_option_vault.to_account_info(),
                     authority: ctx.accounts.signer.to_account_info(),
                 }
             ),
             amount,
         )?;
+        ctx.accounts.pool.owner = ctx.accounts.signer.key();
         ctx.accounts.pool.base_price = base_price;
         ctx.accounts.pool.option_mint = ctx.accounts.option_mint.key();
-        ctx.accounts.pool.left = amount;
-        ctx.accounts.pool.right = 0;
+        ctx.accounts.pool.quote_mint = ctx.accounts.option_data_account.quote_mint;
+        ctx.accounts.pool.option_reserve = amount;
+        ctx.accounts.pool.quote_reserve = 0;
+        ctx.accounts.pool.virtual_quote_reserve = virtual_quote_reserve;
+        ctx.accounts.pool.option_target = amount;
+        ctx.accounts.pool.curve = curve;
+        ctx.accounts.pool.oracle = ctx.accounts.oracle.key();
+        // the opening deposit mints one lp share per option unit
+        mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.user_lp_token_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            amount,
+        )?;
         Ok(())
     }
-    pub fn swap_pool(ctx: Context<SwapPool>, base_price: u64, amount: u64, left_to_right: bool) -> Result<()> {
-        let temp = ctx.accounts.pool.right;
-        let price: u64 = match temp.checked_div(ctx.accounts.pool.left) {
-            None => if ctx.accounts.pool.left == 0 { 
-                return Err(CustomError::PoolEmpty.into())
-            } else { 
-                base_price
-            },
-            Some(val) => val + base_price,
-        };
-        if left_to_right {
-            // swap left to right
-            match ctx.accounts.pool.left.checked_sub(amount) {
-                None => return Err(CustomError::PoolEmpty.into()),
-                Some(_) => true,
-            };
-            match ctx.accounts.pool.right.checked_add(amount) {
-                None => return Err(CustomError::PoolFull.into()),
-                Some(_) => true,
-            };
+    // deposits `amount` option units and quote in proportion to the reserves, minting lp shares pro-rata.
+    // the virtual reserve grows with the deposit so the pool price is unchanged. deposits stop at expiry
+    pub fn add_liquidity(ctx: Context<AddLiquidity>, _base_price: u64, amount: u64) -> Result<()> {
+        let time = Clock::get()?.unix_timestamp as u64;
+        if time > ctx.accounts.option_data_account.end_time {
+            return Err(CustomError::OptionExpired.into())
+        }
+        let option_reserve = ctx.accounts.pool.option_reserve;
+        if option_reserve == 0 {
+            return Err(CustomError::PoolEmpty.into())
+        }
+        let quote_in = mul_div(ctx.accounts.pool.quote_reserve, amount, option_reserve, Rounding::Up)?;
+        let virtual_quote = mul_div(ctx.accounts.pool.virtual_quote_reserve, amount, option_reserve, Rounding::Down)?;
+        let target = mul_div(ctx.accounts.pool.option_target, amount, option_reserve, Rounding::Down)?;
+        let shares = mul_div(ctx.accounts.lp_mint.supply, amount, option_reserve, Rounding::Down)?;
+        if shares == 0 {
+            return Err(CustomError::InvalidLiquidityAmount.into())
+        }
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user_option_token_account.to_account_info(),
+                    to: ctx.accounts.pool_option_vault.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info(),
+                }
+            ),
+            amount,
+        )?;
+        if quote_in > 0 {
+            transfer(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.user_quote_token_account.to_account_info(),
+                        to: ctx.accounts.pool_quote_vault.to_account_info(),
+                        authority: ctx.accounts.signer.to_account_info(),
+                    }
+                ),
+                quote_in,
+            )?;
+        }
+        mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.user_lp_token_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            shares,
+        )?;
+        ctx.accounts.pool.option_reserve = checked_add(option_reserve, amount)?;
+        ctx.accounts.pool.quote_reserve = checked_add(ctx.accounts.pool.quote_reserve, quote_in)?;
+        ctx.accounts.pool.virtual_quote_reserve = checked_add(ctx.accounts.pool.virtual_quote_reserve, virtual_quote)?;
+        ctx.accounts.pool.option_target = checked_add(ctx.accounts.pool.option_target, target)?;
+        Ok(())
+    }
+    // burns `shares` lp tokens for their pro-rata part of both reserves, fees included
+    pub fn remove_liquidity(ctx: Context<RemoveLiquidity>, _base_price: u64, shares: u64) -> Result<()> {
+        let supply = ctx.accounts.lp_mint.supply;
+        if shares == 0 || shares > ctx.accounts.user_lp_token_account.amount {
+            return Err(CustomError::InvalidLiquidityAmount.into())
+        }
+        let options_out = mul_div(ctx.accounts.pool.option_reserve, shares, supply, Rounding::Down)?;
+        let quote_out = mul_div(ctx.accounts.pool.quote_reserve, shares, supply, Rounding::Down)?;
+        let virtual_quote = mul_div(ctx.accounts.pool.virtual_quote_reserve, shares, supply, Rounding::Up)?;
+        let target = mul_div(ctx.accounts.pool.option_target, shares, supply, Rounding::Up)?;
+        burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    from: ctx.accounts.user_lp_token_account.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info(),
+                }
+            ),
+            shares,
+        )?;
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.pool_option_vault.to_account_info(),
+                    to: ctx.accounts.user_option_token_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            options_out,
+        )?;
+        if quote_out > 0 {
             transfer(
                 CpiContext::new_with_signer(
                     ctx.accounts.token_program.to_account_info(),
                     Transfer {
-                        from: ctx.accounts.program_holder_account.to_account_info(),
-                        to: ctx.accounts.user_option_token_account.to_account_info(),
+                        from: ctx.accounts.pool_quote_vault.to_account_info(),
+                        to: ctx.accounts.user_quote_token_account.to_account_info(),
                         authority: ctx.accounts.program_authority.to_account_info(),
                     },
                     &[&[b"auth", &[ctx.bumps.program_authority]]]
                 ),
-                amount,
+                quote_out,
             )?;
-            anchor_lang::system_program::transfer(
+        }
+        ctx.accounts.pool.option_reserve = checked_sub(ctx.accounts.pool.option_reserve, options_out)?;
+        ctx.accounts.pool.quote_reserve = checked_sub(ctx.accounts.pool.quote_reserve, quote_out)?;
+        ctx.accounts.pool.virtual_quote_reserve = ctx.accounts.pool.virtual_quote_reserve.saturating_sub(virtual_quote);
+        ctx.accounts.pool.option_target = ctx.accounts.pool.option_target.saturating_sub(target);
+        Ok(())
+    }
+    // `buy` takes `amount` option units out of the pool for quote, otherwise sells them into it.
+    // the pool fee is charged on the quote leg, lps keep their share of it in the quote reserve.
+    // buys pay at most `max_quote_in` and sells receive at least `min_quote_out`, fees included.
+    // pools stop trading once the series expires
+    #[allow(clippy::too_many_arguments)]
+    pub fn swap_pool(ctx: Context<SwapPool>, _base_price: u64, amount: u64, buy: bool, max_quote_in: u64, min_quote_out: u64, deadline: Option<u64>) -> Result<()> {
+        check_deadline(deadline)?;
+        let time = Clock::get()?.unix_timestamp as u64;
+        if time > ctx.accounts.option_data_account.end_time {
+            return Err(CustomError::OptionExpired.into())
+        }
+        let pricer = PoolPricer::new(&ctx.accounts.pool, &ctx.accounts.option_data_account, &ctx.accounts.oracle)?;
+        let quote = pricer.quote(amount, buy)?;
+        let fee = fee_for(quote, POOL_FEE_BASIS_POINTS)?;
+        if buy && checked_add(quote, fee)? > max_quote_in {
+            return Err(CustomError::SlippageExceeded.into())
+        }
+        if !buy && checked_sub(quote, fee)? < min_quote_out {
+            return Err(CustomError::SlippageExceeded.into())
+        }
+        settle_swap(&ctx, amount, quote, fee, buy)?;
+        apply_swap(&mut ctx.accounts.pool, amount, quote, fee, buy)?;
+        let price = pricer.marginal_price(&ctx.accounts.pool, ctx.accounts.option_data_account.price_scale)?;
+        ctx.accounts.pool.observe(Clock::get()?.unix_timestamp as u64, price)
+    }
+    // buys spend exactly `quote_amount` for at least `option_limit` option units,
+    // sells receive exactly `quote_amount` for at most `option_limit` option units, fees included.
+    // the curve is solved for the option amount and any rounding left over stays with the lps
+    pub fn swap_pool_exact_quote(ctx: Context<SwapPool>, _base_price: u64, quote_amount: u64, buy: bool, option_limit: u64, deadline: Option<u64>) -> Result<()> {
+        check_deadline(deadline)?;
+        let time = Clock::get()?.unix_timestamp as u64;
+        if time > ctx.accounts.option_data_account.end_time {
+            return Err(CustomError::OptionExpired.into())
+        }
+        let pricer = PoolPricer::new(&ctx.accounts.pool, &ctx.accounts.option_data_account, &ctx.accounts.oracle)?;
+        let max_amount = if buy { pricer.max_buy() } else { option_limit.min(ctx.accounts.user_option_token_account.amount) };
+        let amount = options_for_quote(&pricer, quote_amount, buy, max_amount)?;
+        if amount == 0 {
+            return Err(CustomError::InvalidSwapAmount.into())
+        }
+        if buy && amount < option_limit {
+            return Err(CustomError::SlippageExceeded.into())
+        }
+        let fee = fee_for(pricer.quote(amount, buy)?, POOL_FEE_BASIS_POINTS)?;
+        // charge or pay exactly `quote_amount`
+        let quote = if buy { checked_sub(quote_amount, fee)? } else { checked_add(quote_amount, fee)? };
+        settle_swap(&ctx, amount, quote, fee, buy)?;
+        apply_swap(&mut ctx.accounts.pool, amount, quote, fee, buy)?;
+        let price = pricer.marginal_price(&ctx.accounts.pool, ctx.accounts.option_data_account.price_scale)?;
+        ctx.accounts.pool.observe(Clock::get()?.unix_timestamp as u64, price)
+    }
+    // time weighted average of the pool's marginal price over the last `window` seconds,
+    // in quote base units per contract. a zero window reads the current marginal price.
+    // the average runs from the latest snapshot at or before the window start, so it may reach
+    // up to `OBSERVATION_INTERVAL_SECONDS` further back
+    pub fn pool_twap(ctx: Context<PoolTwap>, _base_price: u64, window: u64) -> Result<u64> {
+        ctx.accounts.pool.twap(Clock::get()?.unix_timestamp as u64, window)
+    }
+    // after expiry the owner burns their lp shares for both reserves and closes the pool.
+    // other lps must remove their liquidity first
+    pub fn close_pool(ctx: Context<ClosePool>, _base_price: u64) -> Result<()> {
+        let time = Clock::get()?.unix_timestamp as u64;
+        if ctx.accounts.option_data_account.end_time >= time {
+            return Err(CustomError::OptionNotExpired.into())
+        }
+        let shares = ctx.accounts.user_lp_token_account.amount;
+        if shares != ctx.accounts.lp_mint.supply {
+            return Err(CustomError::OutstandingLiquidity.into())
+        }
+        if shares > 0 {
+            burn(
                 CpiContext::new(
-                    ctx.accounts.system_program.to_account_info(),
-                    anchor_lang::system_program::Transfer {
-                        from: ctx.accounts.signer.to_account_info(),
-                        to: ctx.accounts.program_authority.to_account_info(),
+                    ctx.accounts.token_program.to_account_info(),
+                    Burn {
+                        mint: ctx.accounts.lp_mint.to_account_info(),
+                        from: ctx.accounts.user_lp_token_account.to_account_info(),
+                        authority: ctx.accounts.signer.to_account_info(),
                     }
                 ),
-                price * amount,
+                shares,
             )?;
-        } else {
-            // swap right to left
-            match ctx.accounts.pool.right.checked_sub(amount) {
-                None => return Err(CustomError::PoolEmpty.into()),
-                Some(_) => true,
-            };
-            match ctx.accounts.pool.left.checked_add(amount) {
-                None => return Err(CustomError::PoolEmpty.into()),
-                Some(_) => true,
-            };
+        }
+        // the vaults only hold this pool's reserves, so they are emptied and closed with it
+        if ctx.accounts.pool_option_vault.amount > 0 {
             transfer(
                 CpiContext::new_with_signer(
                     ctx.accounts.token_program.to_account_info(),
                     Transfer {
-                        from: ctx.accounts.user_option_token_account.to_account_info(),
-                        to: ctx.accounts.program_holder_account.to_account_info(),
+                        from: ctx.accounts.pool_option_vault.to_account_info(),
+                        to: ctx.accounts.user_option_token_account.to_account_info(),
                         authority: ctx.accounts.program_authority.to_account_info(),
                     },
                     &[&[b"auth", &[ctx.bumps.program_authority]]]
                 ),
-                amount,
+                ctx.accounts.pool_option_vault.amount,
             )?;
-            let transferred = amount * price;
-            **ctx.accounts.program_authority.try_borrow_mut_lamports()? -= transferred;
-            **ctx.accounts.signer.try_borrow_mut_lamports()? += transferred;
         }
-        Ok(())
-    }
-    pub fn close_pool(ctx: Context<ClosePool>, base_price: u64) -> Result<()> {
-        let time = Clock::get()?.unix_timestamp as u64;
-        let valid = match OptionDataAccount::try_from_slice(&ctx.accounts.option_data_account.data.borrow()).ok() {
-            None => true,
-            Some(account) => account.end_time < time,
-        }; 
-        if !valid {
-            return Err(CustomError::OptionNotExpired.into())
+        if ctx.accounts.pool_quote_vault.amount > 0 {
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.pool_quote_vault.to_account_info(),
+                        to: ctx.accounts.user_quote_token_account.to_account_info(),
+                        authority: ctx.accounts.program_authority.to_account_info(),
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+                ctx.accounts.pool_quote_vault.amount,
+            )?;
+        }
+        for vault in [&ctx.accounts.pool_option_vault, &ctx.accounts.pool_quote_vault] {
+            close_account(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    CloseAccount {
+                        account: vault.to_account_info(),
+                        destination: ctx.accounts.signer.to_account_info(),
+                        authority: ctx.accounts.program_authority.to_account_info(),
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+            )?;
         }
         Ok(())
     }
-    pub fn list(ctx: Context<List>, amount: u64, price: u64) -> Result<()> {
+    // listing more at the same price adds to the seller's listing, `good_til` applies to all of it
+    pub fn list(ctx: Context<List>, amount: u64, price: u64, good_til: Option<u64>) -> Result<()> {
+        if ctx.accounts.list_account.auction.is_some() {
+            return Err(CustomError::InvalidAuction.into())
+        }
         transfer(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 Transfer {
                     from: ctx.accounts.user_option_token_account.to_account_info(),
-                    to: ctx.accounts.program_holder_account.to_account_info(),
+                    to: ctx.accounts.listing_vault.to_account_info(),
                     authority: ctx.accounts.signer.to_account_info(),
                 }
             ),
@@ -195,170 +540,1232 @@ pub mod options {
         ctx.accounts.list_account.price = price;
         ctx.accounts.list_account.owner = ctx.accounts.signer.key();
         ctx.accounts.list_account.underlying_mint = ctx.accounts.option_data_account.underlying_mint.key();
+        ctx.accounts.list_account.quote_mint = ctx.accounts.option_data_account.quote_mint.key();
         ctx.accounts.list_account.option_mint = ctx.accounts.option_mint.key();
+        ctx.accounts.list_account.good_til = good_til;
         Ok(())
     }
-    pub fn buy(ctx: Context<Buy>, _price: u64, amount: u64) -> Result<()> {
-        let ix = anchor_lang::solana_program::system_instruction::transfer(
-            &ctx.accounts.signer.key(),
-            &ctx.accounts.owner.key(),
-            ctx.accounts.listing.price * amount,
-        );
-        anchor_lang::solana_program::program::invoke(
-            &ix,
-            &[
-                ctx.accounts.signer.to_account_info(),
-                ctx.accounts.owner.to_account_info(),
-            ],
-        )?;
-        match ctx.accounts.listing.amount.checked_sub(amount) {
-            None => return Err(CustomError::ListingEmpty.into()),
-            Some(_) => true,
-        };
+    // lists `amount` option units in a dutch auction starting at `price`, keyed like a fixed price listing at it.
+    // the auction must open a new listing, it can't be added to
+    pub fn list_auction(ctx: Context<List>, amount: u64, price: u64, auction: DutchAuction, good_til: Option<u64>) -> Result<()> {
+        if ctx.accounts.list_account.amount > 0
+            || auction.duration == 0
+            || auction.end_price > price
+            || (auction.decay == AuctionDecay::Exponential && (auction.end_price as u128) * MAX_DECAY_RATIO < price as u128) {
+            return Err(CustomError::InvalidAuction.into())
+        }
         transfer(
-            CpiContext::new_with_signer(
+            CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 Transfer {
-                    from: ctx.accounts.program_holder_account.to_account_info(),
-                    to: ctx.accounts.user_holder_account.to_account_info(),
-                    authority: ctx.accounts.program_authority.to_account_info(),
-                },
-                &[&[b"auth", &[ctx.bumps.program_authority]]]
+                    from: ctx.accounts.user_option_token_account.to_account_info(),
+                    to: ctx.accounts.listing_vault.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info(),
+                }
             ),
             amount,
         )?;
+        ctx.accounts.list_account.amount = amount;
+        ctx.accounts.list_account.price = price;
+        ctx.accounts.list_account.owner = ctx.accounts.signer.key();
+        ctx.accounts.list_account.underlying_mint = ctx.accounts.option_data_account.underlying_mint.key();
+        ctx.accounts.list_account.quote_mint = ctx.accounts.option_data_account.quote_mint.key();
+        ctx.accounts.list_account.option_mint = ctx.accounts.option_mint.key();
+        ctx.accounts.list_account.good_til = good_til;
+        ctx.accounts.list_account.auction = Some(auction);
         Ok(())
     }
-    pub fn close_listing(ctx: Context<CloseListing>, price: u64) -> Result<()> {
+    // fills `amount` option units of the listing at its current price, the rest stays listed.
+    // the listing fee comes out of the seller's proceeds, the buyer pays at most `max_quote_in`.
+    // a fully filled listing is closed and its rent returned to the seller
+    pub fn buy(ctx: Context<Buy>, _price: u64, amount: u64, max_quote_in: u64, deadline: Option<u64>) -> Result<()> {
+        check_deadline(deadline)?;
         let time = Clock::get()?.unix_timestamp as u64;
-        let valid = match OptionDataAccount::try_from_slice(&ctx.accounts.option_data_account.data.borrow()).ok() {
-            None => true,
-            Some(account) => account.end_time < time,
-        }; 
-        if !valid || !(ctx.accounts.owner.key() == ctx.accounts.signer.key()) {
-            return Err(CustomError::OptionNotExpired.into())
+        if ctx.accounts.listing.is_expired(time) {
+            return Err(CustomError::ListingExpired.into())
+        }
+        // listings of expired series wait to be swept, their options are worthless
+        if time > ctx.accounts.option_data_account.end_time {
+            return Err(CustomError::OptionExpired.into())
+        }
+        let remaining = match ctx.accounts.listing.amount.checked_sub(amount) {
+            None => return Err(CustomError::ListingEmpty.into()),
+            Some(num) => num,
+        };
+        let price = ctx.accounts.listing.current_price(time)?;
+        let cost = quote_for_options(amount, price, PRICE_SCALE, Rounding::Up)?;
+        if cost > max_quote_in {
+            return Err(CustomError::SlippageExceeded.into())
         }
+        let fee = fee_for(cost, LIST_FEE_BASIS_POINTS)?;
         transfer(
-            CpiContext::new_with_signer(
+            CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 Transfer {
-                    from: ctx.accounts.program_holder_account.to_account_info(),
-                    to: ctx.accounts.owner_token_account.to_account_info(),
-                    authority: ctx.accounts.program_authority.to_account_info(),
-                },
-                &[&[b"auth", &[ctx.bumps.program_authority]]]
+                    from: ctx.accounts.user_quote_token_account.to_account_info(),
+                    to: ctx.accounts.owner_quote_token_account.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info(),
+                }
             ),
-            ctx.accounts.listing.amount,
+            checked_sub(cost, fee)?,
         )?;
-        Ok(())  
-    }
-    pub fn exercise(ctx: Context<Exercise>, amount: u64) -> Result<()> {
-        let time = Clock::get()?.unix_timestamp as u64;
-        if time > ctx.accounts.option_data_account.end_time {
-            return Err(CustomError::OptionExpired.into())
-        }
-        // need to put creator in account
-        if ctx.accounts.option_data_account.call {
-            let ix = anchor_lang::solana_program::system_instruction::transfer(
-                &ctx.accounts.signer.key(),
-                &ctx.accounts.creator.key(),
-                ctx.accounts.option_data_account.strike_price * amount,
-            );
-            anchor_lang::solana_program::program::invoke(
-                &ix,
-                &[
-                    ctx.accounts.signer.to_account_info(),
-                    ctx.accounts.creator.to_account_info(),
-                ],
-            )?;
-            transfer(
-                CpiContext::new_with_signer(
-                    ctx.accounts.token_program.to_account_info(),
-                    Transfer {
-                        from: ctx.accounts.underlying_token_account.to_account_info(),
-                        to: ctx.accounts.user_underlying_token_account.to_account_info(),
-                        authority: ctx.accounts.program_authority.to_account_info()
-                    },
-                    &[&[b"auth", &[ctx.bumps.program_authority]]]
-                ),
-                amount
-            )?;
-        } else {
+        if fee > 0 {
             transfer(
                 CpiContext::new(
                     ctx.accounts.token_program.to_account_info(),
                     Transfer {
-                        from: ctx.accounts.user_underlying_token_account.to_account_info(),
-                        to: ctx.accounts.creator_token_account.to_account_info(),
-                        authority: ctx.accounts.signer.to_account_info()
+                        from: ctx.accounts.user_quote_token_account.to_account_info(),
+                        to: ctx.accounts.treasury_vault.to_account_info(),
+                        authority: ctx.accounts.signer.to_account_info(),
                     }
                 ),
-                amount
+                fee,
             )?;
-            // now transfer sol from holder account to user
-            let transferred = amount * ctx.accounts.option_data_account.strike_price;
-            **ctx.accounts.program_authority.try_borrow_mut_lamports()? -= transferred;
-            **ctx.accounts.signer.try_borrow_mut_lamports()? += transferred;
-        }
-        // burn(
-        //     CpiContext::new_with_signer(
-        //         ctx.accounts.token_program.to_account_info(),
-        //         Burn {
-        //             mint: ctx.accounts.option_mint.to_account_info(),
-        //             from: ctx.accounts.user_option_token_account.to_account_info(),
-        //             authority: ctx.accounts.program_authority.to_account_info()
-        //         },
-        //         &[&[b"auth", &[ctx.bumps.program_authority]]]
-        //     ),
-        //     amount,
-        // )?;
-        ctx.accounts.option_data_account.amount_unexercised = match ctx.accounts.option_data_account.amount_unexercised.checked_sub(amount) {
-            None => return Err(CustomError::NotEnoughOptionToken.into()),
-            Some(num) => num        
-        };
-        Ok(())
-    }
-    pub fn claim(ctx: Context<Claim>) -> Result<()> {
-        if ctx.accounts.signer.key() != ctx.accounts.option_data_account.creator {
-            return Err(CustomError::WrongOwner.into())
-        }
-        let time = Clock::get()?.unix_timestamp as u64;
-        if time < ctx.accounts.option_data_account.end_time {
-            return Err(CustomError::OptionNotExpired.into())
         }
-        if ctx.accounts.option_data_account.call {
-            transfer(
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.listing_vault.to_account_info(),
+                    to: ctx.accounts.user_holder_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            amount,
+        )?;
+        ctx.accounts.listing.amount = remaining;
+        emit!(ListingFilled {
+            listing: ctx.accounts.listing.key(),
+            option_mint: ctx.accounts.option_mint.key(),
+            seller: ctx.accounts.owner.key(),
+            buyer: ctx.accounts.signer.key(),
+            price,
+            amount,
+            cost,
+            fee,
+            remaining,
+        });
+        if remaining == 0 {
+            // the vault only ever holds the listed amount, so it is empty too
+            close_account(
                 CpiContext::new_with_signer(
                     ctx.accounts.token_program.to_account_info(),
-                    Transfer {
-                        from: ctx.accounts.program_holder_account.to_account_info(),
-                        to: ctx.accounts.user_underlying_account.to_account_info(),
-                        authority: ctx.accounts.program_authority.to_account_info()
+                    CloseAccount {
+                        account: ctx.accounts.listing_vault.to_account_info(),
+                        destination: ctx.accounts.owner.to_account_info(),
+                        authority: ctx.accounts.program_authority.to_account_info(),
                     },
                     &[&[b"auth", &[ctx.bumps.program_authority]]]
                 ),
-                ctx.accounts.option_data_account.amount_unexercised
             )?;
-        } else {
-            let transferred = ctx.accounts.option_data_account.amount_unexercised * ctx.accounts.option_data_account.strike_price;
-            **ctx.accounts.program_authority.try_borrow_mut_lamports()? -= transferred;
-            **ctx.accounts.signer.try_borrow_mut_lamports()? += transferred;
+            ctx.accounts.listing.close(ctx.accounts.owner.to_account_info())?;
         }
         Ok(())
     }
-}
-#[error_code]
-pub enum CustomError {
-    #[msg("Strike price not reached")]
-    StrikePriceNotReached,
-    #[msg("Token price not found")]
-    TokenPriceNotFound,
-    #[msg("Listing empty")]
-    ListingEmpty,
-    #[msg("Wrong owner")]
-    WrongOwner,
-    #[msg("Not enough option token")]
-    NotEnoughOptionToken,
+    // cancels the listing at any time, returning the options and rent to the seller
+    pub fn close_listing(ctx: Context<CloseListing>, _price: u64) -> Result<()> {
+        if ctx.accounts.owner.key() != ctx.accounts.signer.key() {
+            return Err(CustomError::WrongOwner.into())
+        }
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.listing_vault.to_account_info(),
+                    to: ctx.accounts.owner_token_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            ctx.accounts.listing_vault.amount,
+        )?;
+        close_account(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                CloseAccount {
+                    account: ctx.accounts.listing_vault.to_account_info(),
+                    destination: ctx.accounts.signer.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+        )?;
+        Ok(())  
+    }
+    // moves the whole listing to the seller's listing at `new_price`, merging with it if it exists.
+    // the moved listing sells at the fixed `new_price`, auctions included
+    pub fn reprice_listing(ctx: Context<RepriceListing>, price: u64, new_price: u64) -> Result<()> {
+        if price == new_price {
+            return Err(CustomError::SamePrice.into())
+        }
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.listing_vault.to_account_info(),
+                    to: ctx.accounts.new_listing_vault.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            ctx.accounts.listing_vault.amount,
+        )?;
+        close_account(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                CloseAccount {
+                    account: ctx.accounts.listing_vault.to_account_info(),
+                    destination: ctx.accounts.signer.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+        )?;
+        let listing = &ctx.accounts.listing;
+        let new_listing = &mut ctx.accounts.new_listing;
+        new_listing.amount = checked_add(new_listing.amount, listing.amount)?;
+        new_listing.price = new_price;
+        new_listing.owner = listing.owner;
+        new_listing.underlying_mint = listing.underlying_mint;
+        new_listing.quote_mint = listing.quote_mint;
+        new_listing.option_mint = listing.option_mint;
+        new_listing.good_til = listing.good_til;
+        new_listing.auction = None;
+        Ok(())
+    }
+    // takes `amount` option units off the listing, an emptied listing is closed
+    pub fn reduce_listing(ctx: Context<ReduceListing>, _price: u64, amount: u64) -> Result<()> {
+        let remaining = match ctx.accounts.listing.amount.checked_sub(amount) {
+            None => return Err(CustomError::ListingEmpty.into()),
+            Some(num) => num,
+        };
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.listing_vault.to_account_info(),
+                    to: ctx.accounts.owner_token_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            amount,
+        )?;
+        ctx.accounts.listing.amount = remaining;
+        if remaining == 0 {
+            close_account(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    CloseAccount {
+                        account: ctx.accounts.listing_vault.to_account_info(),
+                        destination: ctx.accounts.signer.to_account_info(),
+                        authority: ctx.accounts.program_authority.to_account_info(),
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+            )?;
+            ctx.accounts.listing.close(ctx.accounts.signer.to_account_info())?;
+        }
+        Ok(())
+    }
+    // closes listings past their good-til time or whose series expired, returning the options and
+    // rent to each seller. every listing is passed as five remaining accounts: the listing, its vault,
+    // its series' data account, the seller's option token account and the seller
+    pub fn sweep_expired_listings<'info>(ctx: Context<'_, '_, 'info, 'info, SweepExpiredListings<'info>>) -> Result<()> {
+        let time = Clock::get()?.unix_timestamp as u64;
+        let groups = ctx.remaining_accounts.chunks_exact(5);
+        if !groups.remainder().is_empty() {
+            return Err(CustomError::InvalidAccount.into())
+        }
+        for accounts in groups {
+            let listing = Account::<Listing>::try_from(&accounts[0])?;
+            let vault = Account::<TokenAccount>::try_from(&accounts[1])?;
+            let series = Account::<OptionDataAccount>::try_from(&accounts[2])?;
+            let owner_token_account = Account::<TokenAccount>::try_from(&accounts[3])?;
+            let owner = &accounts[4];
+            let (vault_key, _) = Pubkey::find_program_address(&[b"listing_vault", listing.key().as_ref()], &crate::ID);
+            let (series_key, _) = Pubkey::find_program_address(&[b"option_data_account", listing.option_mint.as_ref()], &crate::ID);
+            if vault.key() != vault_key || series.key() != series_key || owner.key() != listing.owner
+                || owner_token_account.owner != listing.owner || owner_token_account.mint != listing.option_mint {
+                return Err(CustomError::InvalidAccount.into())
+            }
+            if !listing.is_expired(time) && series.end_time >= time {
+                return Err(CustomError::ListingNotExpired.into())
+            }
+            if vault.amount > 0 {
+                transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Transfer {
+                            from: vault.to_account_info(),
+                            to: owner_token_account.to_account_info(),
+                            authority: ctx.accounts.program_authority.to_account_info(),
+                        },
+                        &[&[b"auth", &[ctx.bumps.program_authority]]]
+                    ),
+                    vault.amount,
+                )?;
+            }
+            close_account(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    CloseAccount {
+                        account: vault.to_account_info(),
+                        destination: owner.clone(),
+                        authority: ctx.accounts.program_authority.to_account_info(),
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+            )?;
+            listing.close(owner.clone())?;
+        }
+        Ok(())
+    }
+    // escrows the quote for `amount` option units at `price` quote per contract.
+    // bids at the same price add to the bidder's existing bid
+    pub fn place_bid(ctx: Context<PlaceBid>, amount: u64, price: u64) -> Result<()> {
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user_quote_token_account.to_account_info(),
+                    to: ctx.accounts.bid_vault.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info(),
+                }
+            ),
+            quote_for_options(amount, price, PRICE_SCALE, Rounding::Up)?,
+        )?;
+        ctx.accounts.bid.amount = checked_add(ctx.accounts.bid.amount, amount)?;
+        ctx.accounts.bid.price = price;
+        ctx.accounts.bid.bidder = ctx.accounts.signer.key();
+        ctx.accounts.bid.option_mint = ctx.accounts.option_mint.key();
+        ctx.accounts.bid.quote_mint = ctx.accounts.quote_mint.key();
+        Ok(())
+    }
+    // returns the escrowed quote and closes the bid
+    pub fn cancel_bid(ctx: Context<CancelBid>, _price: u64) -> Result<()> {
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.bid_vault.to_account_info(),
+                    to: ctx.accounts.user_quote_token_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            ctx.accounts.bid_vault.amount,
+        )?;
+        close_account(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                CloseAccount {
+                    account: ctx.accounts.bid_vault.to_account_info(),
+                    destination: ctx.accounts.signer.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+        )?;
+        Ok(())
+    }
+    // sells `amount` option units into the bid, delivered straight to the bidder.
+    // the seller receives at least `min_quote_out` after the listing fee.
+    // a fully filled bid is closed, any rounding left in escrow and the rent go back to the bidder
+    pub fn sell_to_bid(ctx: Context<SellToBid>, _price: u64, amount: u64, min_quote_out: u64, deadline: Option<u64>) -> Result<()> {
+        check_deadline(deadline)?;
+        // expired options are worthless, resting bids can only be cancelled
+        if Clock::get()?.unix_timestamp as u64 > ctx.accounts.option_data_account.end_time {
+            return Err(CustomError::OptionExpired.into())
+        }
+        let remaining = match ctx.accounts.bid.amount.checked_sub(amount) {
+            None => return Err(CustomError::BidEmpty.into()),
+            Some(num) => num,
+        };
+        // escrow was rounded up, fills round down so it always covers them
+        let proceeds = quote_for_options(amount, ctx.accounts.bid.price, PRICE_SCALE, Rounding::Down)?;
+        let fee = fee_for(proceeds, LIST_FEE_BASIS_POINTS)?;
+        if checked_sub(proceeds, fee)? < min_quote_out {
+            return Err(CustomError::SlippageExceeded.into())
+        }
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user_option_token_account.to_account_info(),
+                    to: ctx.accounts.bidder_option_token_account.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info(),
+                }
+            ),
+            amount,
+        )?;
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.bid_vault.to_account_info(),
+                    to: ctx.accounts.user_quote_token_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            proceeds - fee,
+        )?;
+        if fee > 0 {
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.bid_vault.to_account_info(),
+                        to: ctx.accounts.treasury_vault.to_account_info(),
+                        authority: ctx.accounts.program_authority.to_account_info(),
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+                fee,
+            )?;
+        }
+        ctx.accounts.bid.amount = remaining;
+        emit!(BidFilled {
+            bid: ctx.accounts.bid.key(),
+            option_mint: ctx.accounts.option_mint.key(),
+            bidder: ctx.accounts.bidder.key(),
+            seller: ctx.accounts.signer.key(),
+            price: ctx.accounts.bid.price,
+            amount,
+            proceeds,
+            fee,
+            remaining,
+        });
+        if remaining == 0 {
+            ctx.accounts.bid_vault.reload()?;
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.bid_vault.to_account_info(),
+                        to: ctx.accounts.bidder_quote_token_account.to_account_info(),
+                        authority: ctx.accounts.program_authority.to_account_info(),
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+                ctx.accounts.bid_vault.amount,
+            )?;
+            close_account(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    CloseAccount {
+                        account: ctx.accounts.bid_vault.to_account_info(),
+                        destination: ctx.accounts.bidder.to_account_info(),
+                        authority: ctx.accounts.program_authority.to_account_info(),
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+            )?;
+            ctx.accounts.bid.close(ctx.accounts.bidder.to_account_info())?;
+        }
+        Ok(())
+    }
+    // opens the order book of a series, with vaults escrowing resting asks and bids
+    pub fn create_order_book(ctx: Context<CreateOrderBook>) -> Result<()> {
+        let mut book = ctx.accounts.order_book.load_init()?;
+        book.option_mint = ctx.accounts.option_mint.key();
+        book.quote_mint = ctx.accounts.quote_mint.key();
+        Ok(())
+    }
+    // matches `amount` option units against the opposite side up to `price`, at the resting prices.
+    // whatever is left rests on the book unless `immediate_or_cancel`, orders that may rest
+    // need at least `MIN_ORDER_AMOUNT`.
+    // the listing fee comes out of the seller's quote, for ask makers when their fill is consumed
+    pub fn place_order(ctx: Context<PlaceOrder>, side: Side, price: u64, amount: u64, immediate_or_cancel: bool) -> Result<()> {
+        if amount == 0 || price == 0 || (!immediate_or_cancel && amount < MIN_ORDER_AMOUNT) {
+            return Err(CustomError::InvalidOrder.into())
+        }
+        // expired options are worthless, resting orders can only be cancelled
+        if Clock::get()?.unix_timestamp as u64 > ctx.accounts.option_data_account.end_time {
+            return Err(CustomError::OptionExpired.into())
+        }
+        let mut book = ctx.accounts.order_book.load_mut()?;
+        let fill = book.match_order(side, price, amount, PRICE_SCALE)?;
+        let resting = if immediate_or_cancel { 0 } else { amount - fill.amount };
+        if resting > 0 {
+            let sequence = book.insert(side, ctx.accounts.signer.key(), price, resting, PRICE_SCALE)?;
+            emit!(OrderPlaced {
+                order_book: ctx.accounts.order_book.key(),
+                owner: ctx.accounts.signer.key(),
+                side,
+                price,
+                amount: resting,
+                sequence,
+            });
+        }
+        drop(book);
+        match side {
+            Side::Bid => {
+                if fill.amount > 0 {
+                    transfer(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.token_program.to_account_info(),
+                            Transfer {
+                                from: ctx.accounts.book_option_vault.to_account_info(),
+                                to: ctx.accounts.user_option_token_account.to_account_info(),
+                                authority: ctx.accounts.program_authority.to_account_info(),
+                            },
+                            &[&[b"auth", &[ctx.bumps.program_authority]]]
+                        ),
+                        fill.amount,
+                    )?;
+                }
+                // pays for the fills and escrows the resting bid
+                let quote_in = checked_add(fill.quote, quote_for_options(resting, price, PRICE_SCALE, Rounding::Up)?)?;
+                if quote_in > 0 {
+                    transfer(
+                        CpiContext::new(
+                            ctx.accounts.token_program.to_account_info(),
+                            Transfer {
+                                from: ctx.accounts.user_quote_token_account.to_account_info(),
+                                to: ctx.accounts.book_quote_vault.to_account_info(),
+                                authority: ctx.accounts.signer.to_account_info(),
+                            }
+                        ),
+                        quote_in,
+                    )?;
+                }
+            }
+            Side::Ask => {
+                let options_in = fill.amount + resting;
+                if options_in > 0 {
+                    transfer(
+                        CpiContext::new(
+                            ctx.accounts.token_program.to_account_info(),
+                            Transfer {
+                                from: ctx.accounts.user_option_token_account.to_account_info(),
+                                to: ctx.accounts.book_option_vault.to_account_info(),
+                                authority: ctx.accounts.signer.to_account_info(),
+                            }
+                        ),
+                        options_in,
+                    )?;
+                }
+                let fee = fee_for(fill.quote, LIST_FEE_BASIS_POINTS)?;
+                if fill.quote > fee {
+                    transfer(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.token_program.to_account_info(),
+                            Transfer {
+                                from: ctx.accounts.book_quote_vault.to_account_info(),
+                                to: ctx.accounts.user_quote_token_account.to_account_info(),
+                                authority: ctx.accounts.program_authority.to_account_info(),
+                            },
+                            &[&[b"auth", &[ctx.bumps.program_authority]]]
+                        ),
+                        fill.quote - fee,
+                    )?;
+                }
+                if fee > 0 {
+                    transfer(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.token_program.to_account_info(),
+                            Transfer {
+                                from: ctx.accounts.book_quote_vault.to_account_info(),
+                                to: ctx.accounts.treasury_vault.to_account_info(),
+                                authority: ctx.accounts.program_authority.to_account_info(),
+                            },
+                            &[&[b"auth", &[ctx.bumps.program_authority]]]
+                        ),
+                        fee,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+    // takes a resting order off the book and returns its escrow to the owner
+    pub fn cancel_order(ctx: Context<CancelOrder>, side: Side, sequence: u64) -> Result<()> {
+        let order = ctx.accounts.order_book.load_mut()?.remove(side, sequence)?;
+        if order.owner != ctx.accounts.signer.key() {
+            return Err(CustomError::WrongOwner.into())
+        }
+        match side {
+            Side::Bid => {
+                transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Transfer {
+                            from: ctx.accounts.book_quote_vault.to_account_info(),
+                            to: ctx.accounts.user_quote_token_account.to_account_info(),
+                            authority: ctx.accounts.program_authority.to_account_info(),
+                        },
+                        &[&[b"auth", &[ctx.bumps.program_authority]]]
+                    ),
+                    quote_for_options(order.amount, order.price, PRICE_SCALE, Rounding::Up)?,
+                )?;
+            }
+            Side::Ask => {
+                transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Transfer {
+                            from: ctx.accounts.book_option_vault.to_account_info(),
+                            to: ctx.accounts.user_option_token_account.to_account_info(),
+                            authority: ctx.accounts.program_authority.to_account_info(),
+                        },
+                        &[&[b"auth", &[ctx.bumps.program_authority]]]
+                    ),
+                    order.amount,
+                )?;
+            }
+        }
+        Ok(())
+    }
+    // pays makers for queued fills and returns the escrow of evicted orders, oldest first.
+    // the maker's receiving token account of each event is passed as a remaining account in queue order:
+    // quote for filled asks and evicted bids, options for filled bids and evicted asks
+    pub fn consume_events<'info>(ctx: Context<'_, '_, 'info, 'info, ConsumeEvents<'info>>) -> Result<()> {
+        let mut events = Vec::new();
+        {
+            let mut book = ctx.accounts.order_book.load_mut()?;
+            for _ in ctx.remaining_accounts {
+                match book.pop_event() {
+                    None => break,
+                    Some(event) => events.push(event),
+                }
+            }
+        }
+        for (event, account) in events.iter().zip(ctx.remaining_accounts) {
+            let destination = Account::<TokenAccount>::try_from(account)?;
+            let pays_options = match event.maker_side() {
+                Side::Bid => !event.is_evicted(),
+                Side::Ask => event.is_evicted(),
+            };
+            let (vault, mint) = if pays_options {
+                (&ctx.accounts.book_option_vault, ctx.accounts.option_mint.key())
+            } else {
+                (&ctx.accounts.book_quote_vault, ctx.accounts.book_quote_vault.mint)
+            };
+            if destination.owner != event.maker || destination.mint != mint {
+                return Err(CustomError::InvalidAccount.into())
+            }
+            // only filled asks pay the listing fee
+            let fee = if event.maker_side() == Side::Ask && !event.is_evicted() {
+                fee_for(event.quote, LIST_FEE_BASIS_POINTS)?
+            } else {
+                0
+            };
+            let payout = if pays_options { event.amount } else { event.quote - fee };
+            if payout > 0 {
+                transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Transfer {
+                            from: vault.to_account_info(),
+                            to: account.clone(),
+                            authority: ctx.accounts.program_authority.to_account_info(),
+                        },
+                        &[&[b"auth", &[ctx.bumps.program_authority]]]
+                    ),
+                    payout,
+                )?;
+            }
+            if fee > 0 {
+                transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Transfer {
+                            from: ctx.accounts.book_quote_vault.to_account_info(),
+                            to: ctx.accounts.treasury_vault.to_account_info(),
+                            authority: ctx.accounts.program_authority.to_account_info(),
+                        },
+                        &[&[b"auth", &[ctx.bumps.program_authority]]]
+                    ),
+                    fee,
+                )?;
+            }
+        }
+        Ok(())
+    }
+    // writer positions to assign the exercise to are passed as remaining accounts, in rotation
+    // order from the series' assignment cursor. positions without open contracts are passed too
+    pub fn exercise<'info>(ctx: Context<'_, '_, 'info, 'info, Exercise<'info>>, amount: u64) -> Result<()> {
+        let time = Clock::get()?.unix_timestamp as u64;
+        if time > ctx.accounts.option_data_account.end_time {
+            return Err(CustomError::OptionExpired.into())
+        }
+        if !ctx.accounts.option_data_account.exercise_style.is_exercisable(time, ctx.accounts.option_data_account.end_time) {
+            return Err(CustomError::OutsideExerciseWindow.into())
+        }
+        if ctx.accounts.user_option_token_account.amount < amount {
+            return Err(CustomError::NotEnoughOptionToken.into())
+        }
+        let series_key = ctx.accounts.option_data_account.key();
+        if ctx.accounts.option_data_account.cash_settled {
+            // pay the holder the intrinsic value out of the assigned writers' collateral
+            let price = read_price(&ctx.accounts.oracle, time)?;
+            let spot = spot_per_contract(&ctx.accounts.option_data_account, price.price, price.expo)?;
+            let intrinsic = intrinsic_per_contract(&ctx.accounts.option_data_account, spot);
+            if intrinsic == 0 {
+                return Err(CustomError::StrikePriceNotReached.into())
+            }
+            assign_exercise(&mut ctx.accounts.option_data_account, series_key, ctx.remaining_accounts, amount, intrinsic, spot)?;
+            let (collateral_vault, collateral_to) = if ctx.accounts.option_data_account.collateral_in_underlying() {
+                (
+                    ctx.accounts.underlying_token_account.to_account_info(),
+                    ctx.accounts.user_underlying_token_account.to_account_info(),
+                )
+            } else {
+                (
+                    ctx.accounts.quote_token_account.to_account_info(),
+                    ctx.accounts.user_quote_token_account.to_account_info(),
+                )
+            };
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: collateral_vault,
+                        to: collateral_to,
+                        authority: ctx.accounts.program_authority.to_account_info()
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+                settlement_for_options(&ctx.accounts.option_data_account, amount, intrinsic, spot, Rounding::Down)?
+            )?;
+        } else if ctx.accounts.option_data_account.call {
+            assign_exercise(&mut ctx.accounts.option_data_account, series_key, ctx.remaining_accounts, amount, 0, 0)?;
+            // strike payments and delivered underlying stay in the vaults until assigned writers claim
+            transfer(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.user_quote_token_account.to_account_info(),
+                        to: ctx.accounts.quote_token_account.to_account_info(),
+                        authority: ctx.accounts.signer.to_account_info()
+                    }
+                ),
+                strike_for_options(&ctx.accounts.option_data_account, amount, Rounding::Up)?
+            )?;
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.underlying_token_account.to_account_info(),
+                        to: ctx.accounts.user_underlying_token_account.to_account_info(),
+                        authority: ctx.accounts.program_authority.to_account_info()
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+                underlying_for_options(&ctx.accounts.option_data_account, amount, Rounding::Down)?
+            )?;
+        } else {
+            assign_exercise(&mut ctx.accounts.option_data_account, series_key, ctx.remaining_accounts, amount, 0, 0)?;
+            transfer(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.user_underlying_token_account.to_account_info(),
+                        to: ctx.accounts.underlying_token_account.to_account_info(),
+                        authority: ctx.accounts.signer.to_account_info()
+                    }
+                ),
+                underlying_for_options(&ctx.accounts.option_data_account, amount, Rounding::Up)?
+            )?;
+            // now transfer quote from the quote vault to user
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.quote_token_account.to_account_info(),
+                        to: ctx.accounts.user_quote_token_account.to_account_info(),
+                        authority: ctx.accounts.program_authority.to_account_info()
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+                strike_for_options(&ctx.accounts.option_data_account, amount, Rounding::Down)?
+            )?;
+        }
+        // burn the exercised options, signed by the holder
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.option_token_program.to_account_info(),
+                token_interface::Burn {
+                    mint: ctx.accounts.option_mint.to_account_info(),
+                    from: ctx.accounts.user_option_token_account.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info()
+                },
+            ),
+            amount,
+        )?;
+        ctx.accounts.option_data_account.amount_unexercised = match ctx.accounts.option_data_account.amount_unexercised.checked_sub(amount) {
+            None => return Err(CustomError::NotEnoughOptionToken.into()),
+            Some(num) => num        
+        };
+        Ok(())
+    }
+    // writer burns options they hold against their own open contracts and takes back the collateral
+    pub fn close_position(ctx: Context<ClosePosition>, amount: u64) -> Result<()> {
+        let position = &ctx.accounts.writer_position;
+        if position.contracts_written - position.contracts_assigned < amount {
+            return Err(CustomError::NotEnoughOpenContracts.into())
+        }
+        if ctx.accounts.user_option_token_account.amount < amount {
+            return Err(CustomError::NotEnoughOptionToken.into())
+        }
+        let collateral = mul_div(position.collateral_deposited, amount, position.contracts_written, Rounding::Down)?;
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.option_token_program.to_account_info(),
+                token_interface::Burn {
+                    mint: ctx.accounts.option_mint.to_account_info(),
+                    from: ctx.accounts.user_option_token_account.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info()
+                },
+            ),
+            amount,
+        )?;
+        let (collateral_vault, collateral_to) = if ctx.accounts.option_data_account.collateral_in_underlying() {
+            (
+                ctx.accounts.underlying_token_account.to_account_info(),
+                ctx.accounts.user_underlying_token_account.to_account_info(),
+            )
+        } else {
+            (
+                ctx.accounts.quote_token_account.to_account_info(),
+                ctx.accounts.user_quote_token_account.to_account_info(),
+            )
+        };
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: collateral_vault,
+                    to: collateral_to,
+                    authority: ctx.accounts.program_authority.to_account_info()
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            collateral
+        )?;
+        ctx.accounts.writer_position.collateral_deposited = checked_sub(ctx.accounts.writer_position.collateral_deposited, collateral)?;
+        ctx.accounts.writer_position.contracts_written = checked_sub(ctx.accounts.writer_position.contracts_written, amount)?;
+        ctx.accounts.option_data_account.amount_written = checked_sub(ctx.accounts.option_data_account.amount_written, amount)?;
+        ctx.accounts.option_data_account.amount_unexercised = checked_sub(ctx.accounts.option_data_account.amount_unexercised, amount)?;
+        Ok(())
+    }
+    // settles the signer's own writer position: their share of the unexercised collateral
+    // plus any proceeds credited to them, once the exercise window has closed
+    pub fn claim(ctx: Context<Claim>) -> Result<()> {
+        let time = Clock::get()?.unix_timestamp as u64;
+        if time <= ctx.accounts.option_data_account.end_time {
+            return Err(CustomError::OptionNotExpired.into())
+        }
+        let position = &ctx.accounts.writer_position;
+        let series = &ctx.accounts.option_data_account;
+        // contracts never assigned to this writer keep their collateral
+        let unexercised = position.contracts_written - position.contracts_assigned;
+        let collateral = if position.contracts_written == 0 {
+            0
+        } else {
+            mul_div(position.collateral_deposited, unexercised, position.contracts_written, Rounding::Down)?
+        };
+        let proceeds = position.proceeds_claimable;
+        // physical calls are collateralized in underlying and paid in quote, physical puts the other way around,
+        // cash settled series return what is left of their collateral: underlying for calls, quote for puts
+        let (collateral_vault, collateral_to, proceeds_vault, proceeds_to) = if series.cash_settled && series.call {
+            (
+                ctx.accounts.underlying_token_account.to_account_info(),
+                ctx.accounts.user_underlying_account.to_account_info(),
+                ctx.accounts.underlying_token_account.to_account_info(),
+                ctx.accounts.user_underlying_account.to_account_info(),
+            )
+        } else if series.cash_settled {
+            (
+                ctx.accounts.quote_token_account.to_account_info(),
+                ctx.accounts.user_quote_account.to_account_info(),
+                ctx.accounts.quote_token_account.to_account_info(),
+                ctx.accounts.user_quote_account.to_account_info(),
+            )
+        } else if series.call {
+            (
+                ctx.accounts.underlying_token_account.to_account_info(),
+                ctx.accounts.user_underlying_account.to_account_info(),
+                ctx.accounts.quote_token_account.to_account_info(),
+                ctx.accounts.user_quote_account.to_account_info(),
+            )
+        } else {
+            (
+                ctx.accounts.quote_token_account.to_account_info(),
+                ctx.accounts.user_quote_account.to_account_info(),
+                ctx.accounts.underlying_token_account.to_account_info(),
+                ctx.accounts.user_underlying_account.to_account_info(),
+            )
+        };
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: collateral_vault,
+                    to: collateral_to,
+                    authority: ctx.accounts.program_authority.to_account_info()
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            collateral
+        )?;
+        if proceeds > 0 {
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: proceeds_vault,
+                        to: proceeds_to,
+                        authority: ctx.accounts.program_authority.to_account_info()
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+                proceeds
+            )?;
+        }
+        ctx.accounts.option_data_account.amount_written = checked_sub(ctx.accounts.option_data_account.amount_written, ctx.accounts.writer_position.contracts_written)?;
+        ctx.accounts.option_data_account.amount_unexercised = checked_sub(ctx.accounts.option_data_account.amount_unexercised, unexercised)?;
+        Ok(())
+    }
+}
+// prices trades against a pool, model values are read off the oracle once per instruction
+enum PoolPricer {
+    ConstantProduct { option_reserve: u64, quote_depth: u64 },
+    Model { fair: u64, skew_bps: u64, target: u64, reserve: u64, price_scale: u64 },
+}
+impl PoolPricer {
+    fn new(pool: &Pool, series: &OptionDataAccount, oracle: &AccountInfo) -> Result<Self> {
+        match pool.curve {
+            PoolCurve::ConstantProduct => Ok(PoolPricer::ConstantProduct {
+                option_reserve: pool.option_reserve,
+                quote_depth: pool.quote_depth()?,
+            }),
+            PoolCurve::BlackScholes { volatility_bps, skew_bps } => {
+                let time = Clock::get()?.unix_timestamp as u64;
+                if time >= series.end_time {
+                    return Err(CustomError::OptionExpired.into())
+                }
+                let price = read_price(oracle, time)?;
+                let spot = spot_per_contract(series, price.price, price.expo)?;
+                Ok(PoolPricer::Model {
+                    fair: fair_value(series, spot, series.end_time - time, volatility_bps)?,
+                    skew_bps,
+                    target: pool.option_target,
+                    reserve: pool.option_reserve,
+                    price_scale: series.price_scale,
+                })
+            }
+        }
+    }
+    // quote for `amount` option units, paid in for buys and out for sells
+    fn quote(&self, amount: u64, buy: bool) -> Result<u64> {
+        match *self {
+            PoolPricer::ConstantProduct { option_reserve, quote_depth } => if buy {
+                quote_in_for_options(option_reserve, quote_depth, amount)
+            } else {
+                quote_out_for_options(option_reserve, quote_depth, amount)
+            },
+            PoolPricer::Model { fair, skew_bps, target, reserve, price_scale } => {
+                if buy && amount > reserve {
+                    return Err(CustomError::PoolEmpty.into())
+                }
+                skewed_quote(fair, skew_bps, target, reserve, amount, buy, price_scale)
+            }
+        }
+    }
+    // quote per contract for the next unit traded once `pool` holds its post trade reserves
+    fn marginal_price(&self, pool: &Pool, price_scale: u64) -> Result<u64> {
+        match *self {
+            PoolPricer::ConstantProduct { .. } => mul_div(pool.quote_depth()?, price_scale, pool.option_reserve, Rounding::Down),
+            PoolPricer::Model { fair, skew_bps, .. } => skewed_price(fair, skew_bps, pool.option_target, pool.option_reserve),
+        }
+    }
+    // most option units a single buy can take out of the pool
+    fn max_buy(&self) -> u64 {
+        match *self {
+            PoolPricer::ConstantProduct { option_reserve, .. } => option_reserve.saturating_sub(1),
+            PoolPricer::Model { reserve, .. } => reserve,
+        }
+    }
+}
+// option units traded for exactly `quote` with fees, searching up to `max_amount`:
+// the most a buy paying `quote` can take, or the fewest a sell must deliver to receive `quote`
+fn options_for_quote(pricer: &PoolPricer, quote: u64, buy: bool, max_amount: u64) -> Result<u64> {
+    let fits = |amount: u64| -> Result<bool> {
+        let curve_quote = pricer.quote(amount, buy)?;
+        let fee = fee_for(curve_quote, POOL_FEE_BASIS_POINTS)?;
+        Ok(if buy { checked_add(curve_quote, fee)? <= quote } else { curve_quote.saturating_sub(fee) >= quote })
+    };
+    let (mut low, mut high) = (0, max_amount);
+    if buy {
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if fits(mid)? { low = mid } else { high = mid - 1 }
+        }
+        Ok(low)
+    } else {
+        if !fits(max_amount)? {
+            return Err(CustomError::SlippageExceeded.into())
+        }
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if fits(mid)? { high = mid } else { low = mid + 1 }
+        }
+        Ok(low)
+    }
+}
+// moves the tokens of a pool trade priced at `quote` with `fee` on top for buys or taken out for sells
+fn settle_swap(ctx: &Context<SwapPool>, amount: u64, quote: u64, fee: u64, buy: bool) -> Result<()> {
+    let protocol_fee = mul_div(fee, PROTOCOL_FEE_SHARE_BASIS_POINTS, 10_000, Rounding::Down)?;
+    if buy {
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.pool_option_vault.to_account_info(),
+                    to: ctx.accounts.user_option_token_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            amount,
+        )?;
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user_quote_token_account.to_account_info(),
+                    to: ctx.accounts.pool_quote_vault.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info(),
+                }
+            ),
+            checked_add(quote, fee - protocol_fee)?,
+        )?;
+        if protocol_fee > 0 {
+            transfer(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.user_quote_token_account.to_account_info(),
+                        to: ctx.accounts.treasury_vault.to_account_info(),
+                        authority: ctx.accounts.signer.to_account_info(),
+                    }
+                ),
+                protocol_fee,
+            )?;
+        }
+    } else {
+        // the virtual reserve only sets the price, it can't be paid out
+        if quote > ctx.accounts.pool.quote_reserve {
+            return Err(CustomError::PoolEmpty.into())
+        }
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user_option_token_account.to_account_info(),
+                    to: ctx.accounts.pool_option_vault.to_account_info(),
+                    authority: ctx.accounts.signer.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.pool_quote_vault.to_account_info(),
+                    to: ctx.accounts.user_quote_token_account.to_account_info(),
+                    authority: ctx.accounts.program_authority.to_account_info(),
+                },
+                &[&[b"auth", &[ctx.bumps.program_authority]]]
+            ),
+            checked_sub(quote, fee)?,
+        )?;
+        if protocol_fee > 0 {
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.pool_quote_vault.to_account_info(),
+                        to: ctx.accounts.treasury_vault.to_account_info(),
+                        authority: ctx.accounts.program_authority.to_account_info(),
+                    },
+                    &[&[b"auth", &[ctx.bumps.program_authority]]]
+                ),
+                protocol_fee,
+            )?;
+        }
+    }
+    Ok(())
+}
+// updates the reserves after a trade settled by `settle_swap`, lps keep their share of the fee
+fn apply_swap(pool: &mut Pool, amount: u64, quote: u64, fee: u64, buy: bool) -> Result<()> {
+    let lp_fee = fee - mul_div(fee, PROTOCOL_FEE_SHARE_BASIS_POINTS, 10_000, Rounding::Down)?;
+    if buy {
+        pool.option_reserve = checked_sub(pool.option_reserve, amount)?;
+        pool.quote_reserve = match pool.quote_reserve.checked_add(quote).and_then(|num| num.checked_add(lp_fee)) {
+            None => return Err(CustomError::PoolFull.into()),
+            Some(num) => num,
+        };
+    } else {
+        pool.option_reserve = match pool.option_reserve.checked_add(amount) {
+            None => return Err(CustomError::PoolFull.into()),
+            Some(num) => num,
+        };
+        pool.quote_reserve = checked_sub(pool.quote_reserve, checked_sub(quote, lp_fee)?)?;
+    }
+    Ok(())
+}
+// option mints of series that can't be resold are non-transferable token-2022 mints
+fn option_token_program_id(resellable: bool) -> Pubkey {
+    if resellable { token::ID } else { token_2022::ID }
+}
+// creates and initializes a series' option mint at its pda, signed by `seeds`
+fn create_option_mint(accounts: &Create, seeds: &[&[u8]], resellable: bool) -> Result<()> {
+    let extensions: &[ExtensionType] = if resellable { &[] } else { &[ExtensionType::NonTransferable] };
+    let space = ExtensionType::try_calculate_account_len::<token_2022::spl_token_2022::state::Mint>(extensions)?;
+    let rent = Rent::get()?.minimum_balance(space);
+    let lamports = accounts.option_mint.lamports();
+    if lamports == 0 {
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                accounts.system_program.to_account_info(),
+                system_program::CreateAccount {
+                    from: accounts.signer.to_account_info(),
+                    to: accounts.option_mint.to_account_info(),
+                },
+                &[seeds]
+            ),
+            rent,
+            space as u64,
+            &accounts.option_token_program.key(),
+        )?;
+    } else {
+        // the pda was funded ahead of time, which would make create_account fail.
+        // top it up to rent exemption and take it over the way anchor's init does
+        if rent > lamports {
+            system_program::transfer(
+                CpiContext::new(
+                    accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: accounts.signer.to_account_info(),
+                        to: accounts.option_mint.to_account_info(),
+                    }
+                ),
+                rent - lamports,
+            )?;
+        }
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                accounts.system_program.to_account_info(),
+                system_program::Allocate {
+                    account_to_allocate: accounts.option_mint.to_account_info(),
+                },
+                &[seeds]
+            ),
+            space as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                accounts.system_program.to_account_info(),
+                system_program::Assign {
+                    account_to_assign: accounts.option_mint.to_account_info(),
+                },
+                &[seeds]
+            ),
+            &accounts.option_token_program.key(),
+        )?;
+    }
+    if !resellable {
+        // extensions are initialized before the mint itself
+        anchor_lang::solana_program::program::invoke(
+            &initialize_non_transferable_mint(&token_2022::ID, &accounts.option_mint.key())?,
+            &[accounts.option_mint.to_account_info()],
+        )?;
+    }
+    token_interface::initialize_mint2(
+        CpiContext::new(
+            accounts.option_token_program.to_account_info(),
+            token_interface::InitializeMint2 {
+                mint: accounts.option_mint.to_account_info(),
+            }
+        ),
+        OPTION_MINT_DECIMALS,
+        &accounts.program_authority.key(),
+        None,
+    )
+}
+// rejects transactions landing after the caller's deadline
+fn check_deadline(deadline: Option<u64>) -> Result<()> {
+    if let Some(deadline) = deadline {
+        if Clock::get()?.unix_timestamp as u64 > deadline {
+            return Err(CustomError::DeadlineExceeded.into())
+        }
+    }
+    Ok(())
+}
+// assigns `amount` exercised contracts round-robin over the writer positions in `positions`,
+// crediting each assigned writer with the strike (calls) or the delivered underlying (puts).
+// for cash settled series writers are credited their collateral less the settlement of `intrinsic` at `spot`.
+// positions must follow the rotation from the assignment cursor without gaps, empty ones included,
+// so the exerciser can't pick writers. the cursor moves past the last one
+fn assign_exercise<'info>(series: &mut OptionDataAccount, series_key: Pubkey, positions: &'info [AccountInfo<'info>], amount: u64, intrinsic: u64, spot: u64) -> Result<()> {
+    let mut remaining = amount;
+    let mut cursor = series.assignment_cursor;
+    // rotation distance from the cursor the next position must be at, so no writer is skipped
+    let mut next_offset = 0;
+    for info in positions {
+        if remaining == 0 {
+            break;
+        }
+        let mut position: Account<'info, WriterPosition> = Account::try_from(info)?;
+        if position.series != series_key {
+            return Err(CustomError::InvalidAccount.into())
+        }
+        let offset = (position.index + series.writer_count - series.assignment_cursor) % series.writer_count;
+        let open = position.contracts_written - position.contracts_assigned;
+        if offset != next_offset {
+            return Err(CustomError::InvalidAccount.into())
+        }
+        // positions without open contracts are passed through and assigned nothing
+        let assigned = remaining.min(open);
+        let proceeds = if series.cash_settled {
+            collateral_for_options(series, assigned, Rounding::Down)?
+                .saturating_sub(settlement_for_options(series, assigned, intrinsic, spot, Rounding::Up)?)
+        } else if series.call {
+            strike_for_options(series, assigned, Rounding::Down)?
+        } else {
+            underlying_for_options(series, assigned, Rounding::Down)?
+        };
+        position.contracts_assigned += assigned;
+        position.proceeds_claimable = checked_add(position.proceeds_claimable, proceeds)?;
+        position.exit(&crate::ID)?;
+        remaining -= assigned;
+        next_offset = offset + 1;
+        cursor = (position.index + 1) % series.writer_count;
+    }
+    if remaining > 0 {
+        return Err(CustomError::AssignmentIncomplete.into())
+    }
+    series.assignment_cursor = cursor;
+    Ok(())
+}
+#[error_code]
+pub enum CustomError {
+    #[msg("Strike price not reached")]
+    StrikePriceNotReached,
+    #[msg("Token price not found")]
+    TokenPriceNotFound,
+    #[msg("Listing empty")]
+    ListingEmpty,
+    #[msg("Wrong owner")]
+    WrongOwner,
+    #[msg("Not enough option token")]
+    NotEnoughOptionToken,
     #[msg("Option expired")]
     OptionExpired,
     #[msg("Option not expired")]
@@ -368,215 +1775,985 @@ pub enum CustomError {
     #[msg("Pool empty")]
     PoolEmpty,
     #[msg("Pool full")]
-    PoolFull
+    PoolFull,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("Not enough writer positions to assign exercise")]
+    AssignmentIncomplete,
+    #[msg("Not enough open contracts in writer position")]
+    NotEnoughOpenContracts,
+    #[msg("Invalid contract multiplier")]
+    InvalidContractMultiplier,
+    #[msg("Invalid exercise dates")]
+    InvalidExerciseDates,
+    #[msg("Option not exercisable at this time")]
+    OutsideExerciseWindow,
+    #[msg("Oracle price is stale")]
+    StalePrice,
+    #[msg("Oracle confidence interval too wide")]
+    PriceConfidenceTooWide,
+    #[msg("Invalid liquidity amount")]
+    InvalidLiquidityAmount,
+    #[msg("Pool has liquidity from other providers")]
+    OutstandingLiquidity,
+    #[msg("Price moved beyond the slippage limit")]
+    SlippageExceeded,
+    #[msg("Transaction deadline exceeded")]
+    DeadlineExceeded,
+    #[msg("Invalid pool curve")]
+    InvalidPoolCurve,
+    #[msg("Swap amount too small")]
+    InvalidSwapAmount,
+    #[msg("Pool price history does not cover the window")]
+    InsufficientPriceHistory,
+    #[msg("Bid does not want that many options")]
+    BidEmpty,
+    #[msg("Orders need a price and an amount, at least the minimum if they may rest")]
+    InvalidOrder,
+    #[msg("Order book side is full")]
+    OrderBookFull,
+    #[msg("Order not found")]
+    OrderNotFound,
+    #[msg("Fill queue is full, consume events first")]
+    EventQueueFull,
+    #[msg("Option series is not resellable")]
+    NotResellable,
+    #[msg("Listing is past its good-til time")]
+    ListingExpired,
+    #[msg("Listing and its series have not expired")]
+    ListingNotExpired,
+    #[msg("Invalid dutch auction")]
+    InvalidAuction,
+    #[msg("Write amount must be positive")]
+    InvalidWriteAmount,
+    #[msg("New price equals the current price")]
+    SamePrice
+}
+#[account]
+pub struct OptionDataAccount {
+    creator: Pubkey,
+    underlying_mint: Pubkey,
+    quote_mint: Pubkey,
+    end_time: u64,
+    // quote base units per contract
+    strike_price: u64,
+    // underlying base units per contract
+    contract_multiplier: u64,
+    // option base units per contract
+    price_scale: u64,
+    amount_unexercised: u64,
+    // contracts written by positions that have not claimed yet
+    amount_written: u64,
+    // number of writer positions ever opened, positions are indexed in that order
+    writer_count: u64,
+    // index of the next writer position to be assigned an exercise
+    assignment_cursor: u64,
+    call: bool,
+    resellable: bool,
+    underlying_decimals: u8,
+    quote_decimals: u8,
+    cash_settled: bool,
+    oracle: Pubkey,
+    exercise_style: ExerciseStyle,
+}
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum ExerciseStyle {
+    // any time up to expiry
+    American,
+    // only in the window before expiry
+    European,
+    // only in the windows before each date
+    Bermudan { dates: Vec<u64> },
+}
+impl ExerciseStyle {
+    fn is_exercisable(&self, time: u64, end_time: u64) -> bool {
+        let in_window = |date: u64| time <= date && time + EXERCISE_WINDOW_SECONDS >= date;
+        match self {
+            ExerciseStyle::American => time <= end_time,
+            ExerciseStyle::European => in_window(end_time),
+            ExerciseStyle::Bermudan { dates } => dates.iter().any(|date| in_window(*date)),
+        }
+    }
+    // series seed component, bermudan dates are part of the terms
+    fn seed(&self) -> [u8; 32] {
+        anchor_lang::solana_program::hash::hash(&self.try_to_vec().unwrap_or_default()).to_bytes()
+    }
+}
+impl OptionDataAccount {
+    fn collateral_in_underlying(&self) -> bool {
+        self.call
+    }
 }
 #[account]
-pub struct OptionDataAccount {
-    creator: Pubkey,
+pub struct WriterPosition {
+    series: Pubkey,
+    writer: Pubkey,
+    index: u64,
+    collateral_deposited: u64,
+    contracts_written: u64,
+    contracts_assigned: u64,
+    proceeds_claimable: u64,
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        seeds = [b"auth"],
+        bump,
+        payer = signer,
+        space = 8
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    #[account(
+        init,
+        seeds = [b"treasury"],
+        bump,
+        payer = signer,
+        space = 8 + 32
+    )]
+    pub treasury: Account<'info, Treasury>,
+    // only the upgrade authority may initialize, so the treasury admin cannot be front-run
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ CustomError::InvalidAccount)]
+    pub program: Program<'info, crate::program::Options>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(signer.key()) @ CustomError::WrongOwner)]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+#[account]
+pub struct Treasury {
+    // may withdraw collected fees
+    admin: Pubkey,
+}
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"treasury"],
+        bump,
+        constraint = treasury.admin == signer.key() @ CustomError::WrongOwner
+    )]
+    pub treasury: Account<'info, Treasury>,
+    pub quote_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"treasury_vault", quote_mint.key().as_ref()],
+        bump,
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = admin_quote_token_account.mint == quote_mint.key() @ CustomError::InvalidAccount
+    )]
+    pub admin_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+#[derive(Accounts)]
+#[instruction(end_time: u64, strike_price: u64, contract_multiplier: u64, amount: u64, call: bool, resellable: bool, cash_settled: bool, exercise_style: ExerciseStyle)]
+pub struct Create<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub underlying_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub user_underlying_token_account: Account<'info, TokenAccount>,
+    pub quote_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    /// CHECK: read by cash settled exercises, the default pubkey for physically settled series
+    pub oracle: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"option_mint",
+            underlying_mint.key().as_ref(),
+            quote_mint.key().as_ref(),
+            strike_price.to_be_bytes().as_ref(),
+            contract_multiplier.to_be_bytes().as_ref(),
+            end_time.to_be_bytes().as_ref(),
+            &[call as u8],
+            &[resellable as u8],
+            &[cash_settled as u8],
+            oracle.key().as_ref(),
+            exercise_style.seed().as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: created in program on the first write, under `option_token_program`
+    pub option_mint: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: the signer's associated account for the option mint, created in program
+    pub user_option_token_account: AccountInfo<'info>,
+    #[account(
+        init_if_needed,
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump,
+        payer = signer,
+        space = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 1 + 1 + 32 + 1 + 4 + 8 * MAX_EXERCISE_DATES,
+    )]
+    pub option_data_account: Account<'info, OptionDataAccount>,
+    // collateral and exercise proceeds of this series only
+    #[account(
+        init_if_needed,
+        payer = signer,
+        seeds = [b"underlying_vault", option_data_account.key().as_ref()],
+        bump,
+        token::authority = program_authority,
+        token::mint = underlying_mint
+    )]
+    pub underlying_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        seeds = [b"quote_vault", option_data_account.key().as_ref()],
+        bump,
+        token::authority = program_authority,
+        token::mint = quote_mint
+    )]
+    pub quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        seeds = [b"writer_position", option_data_account.key().as_ref(), signer.key().as_ref()],
+        bump,
+        payer = signer,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8,
+    )]
+    pub writer_position: Account<'info, WriterPosition>,
+    #[account(
+        mut,
+        seeds = [b"auth"],
+        bump
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    // token-2022 for series that can't be resold, the token program otherwise
+    #[account(address = option_token_program_id(resellable) @ CustomError::InvalidAccount)]
+    pub option_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+#[account]
+pub struct Listing {
     underlying_mint: Pubkey,
-    end_time: u64,
-    strike_price: u64,
-    amount_unexercised: u64,
-    call: bool,
-    resellable: bool,
+    quote_mint: Pubkey,
+    option_mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+    price: u64,
+    // unix time after which the listing can't be bought and may be swept
+    good_til: Option<u64>,
+    // price decay of dutch auction listings, which start at `price`
+    auction: Option<DutchAuction>,
+}
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct DutchAuction {
+    // quote base units per contract once the auction is over
+    end_price: u64,
+    start_time: u64,
+    // seconds from `start_time` to reach `end_price`
+    duration: u64,
+    decay: AuctionDecay,
+}
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AuctionDecay {
+    // drops by the same amount every second
+    Linear,
+    // drops by the same fraction every second
+    Exponential,
+}
+impl Listing {
+    fn is_expired(&self, time: u64) -> bool {
+        matches!(self.good_til, Some(good_til) if time > good_til)
+    }
+    // quote base units per contract at `time`, auctions hold their start price until they start
+    // and round up so the seller never gets less than the curve
+    fn current_price(&self, time: u64) -> Result<u64> {
+        let auction = match self.auction {
+            None => return Ok(self.price),
+            Some(auction) => auction,
+        };
+        let elapsed = time.saturating_sub(auction.start_time).min(auction.duration);
+        match auction.decay {
+            AuctionDecay::Linear => checked_sub(self.price, mul_div(self.price - auction.end_price, elapsed, auction.duration, Rounding::Down)?),
+            AuctionDecay::Exponential => exponential_decay(self.price, auction.end_price, elapsed, auction.duration),
+        }
+    }
+}
+#[account]
+pub struct Bid {
+    option_mint: Pubkey,
+    quote_mint: Pubkey,
+    bidder: Pubkey,
+    // option units still wanted
+    amount: u64,
+    // quote base units per contract
+    price: u64,
+}
+// emitted when an order rests on a book, its sequence is needed to cancel it
+#[event]
+pub struct OrderPlaced {
+    pub order_book: Pubkey,
+    pub owner: Pubkey,
+    pub side: Side,
+    pub price: u64,
+    pub amount: u64,
+    pub sequence: u64,
+}
+// emitted for every fill of a bid
+#[event]
+pub struct BidFilled {
+    pub bid: Pubkey,
+    pub option_mint: Pubkey,
+    pub bidder: Pubkey,
+    pub seller: Pubkey,
+    // quote base units per contract
+    pub price: u64,
+    pub amount: u64,
+    // quote released from escrow, the fee included
+    pub proceeds: u64,
+    pub fee: u64,
+    // option units the bid still wants
+    pub remaining: u64,
+}
+// emitted for every fill of a listing
+#[event]
+pub struct ListingFilled {
+    pub listing: Pubkey,
+    pub option_mint: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    // quote base units per contract
+    pub price: u64,
+    pub amount: u64,
+    // quote paid by the buyer, the fee included
+    pub cost: u64,
+    pub fee: u64,
+    // option units left on the listing
+    pub remaining: u64,
+}
+#[derive(Accounts)]
+pub struct InitMockOracle<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"treasury"],
+        bump,
+        constraint = treasury.admin == signer.key() @ CustomError::WrongOwner
+    )]
+    pub treasury: Account<'info, Treasury>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + 32 + 8 + 8 + 4 + 8,
+    )]
+    pub mock_oracle: Account<'info, MockOracle>,
+    pub system_program: Program<'info, System>,
+}
+#[derive(Accounts)]
+pub struct SetMockPrice<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = mock_oracle.authority == signer.key() @ CustomError::WrongOwner
+    )]
+    pub mock_oracle: Account<'info, MockOracle>,
+}
+#[derive(Accounts)]
+#[instruction(amount: u64, price: u64)]
+pub struct List<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub option_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub user_option_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump,
+        constraint = option_data_account.resellable @ CustomError::NotResellable,
+    )]
+    pub option_data_account: Account<'info, OptionDataAccount>,
+    #[account(address = option_data_account.quote_mint @ CustomError::InvalidAccount)]
+    pub quote_mint: Account<'info, Mint>,
+    // collects the listing fee on fills
+    #[account(
+        init_if_needed,
+        payer = signer,
+        seeds = [b"treasury_vault", quote_mint.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = program_authority,
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        seeds = [b"listing", option_mint.key().as_ref(), signer.key().as_ref(), price.to_be_bytes().as_ref()],
+        bump,
+        space = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 1 + 8 + 1 + 8 + 8 + 8 + 1
+    )]
+    pub list_account: Account<'info, Listing>,
+    // holds the options listed by this listing only
+    #[account(
+        init_if_needed,
+        payer = signer,
+        seeds = [b"listing_vault", list_account.key().as_ref()],
+        bump,
+        token::mint = option_mint,
+        token::authority = program_authority,
+    )]
+    pub listing_vault: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+#[derive(Accounts)]
+#[instruction(price: u64)]
+pub struct Buy<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub option_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump,
+    )]
+    pub option_data_account: Account<'info, OptionDataAccount>,
+    #[account(mut)]
+    /// CHECK: the listing seeds tie it to the listing owner, receives the rent once filled
+    pub owner: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"listing", option_mint.key().as_ref(), owner.key().as_ref(), price.to_be_bytes().as_ref()],
+        bump,
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        mut,
+        seeds = [b"listing_vault", listing.key().as_ref()],
+        bump,
+    )]
+    pub listing_vault: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_holder_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_quote_token_account.mint == listing.quote_mint @ CustomError::InvalidAccount
+    )]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = owner_quote_token_account.owner == owner.key() @ CustomError::InvalidAccount,
+        constraint = owner_quote_token_account.mint == listing.quote_mint @ CustomError::InvalidAccount
+    )]
+    pub owner_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"treasury_vault", listing.quote_mint.as_ref()],
+        bump,
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+#[derive(Accounts)]
+#[instruction(price: u64)]
+pub struct CloseListing<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub option_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"listing", option_mint.key().as_ref(), owner.key().as_ref(), price.to_be_bytes().as_ref()],
+        bump,
+        close = signer,
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        mut,
+        seeds = [b"listing_vault", listing.key().as_ref()],
+        bump,
+    )]
+    pub listing_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = owner.key() == listing.owner @ CustomError::InvalidAccount
+    )]
+    /// CHECK: 
+    pub owner: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = owner.key() == owner_token_account.owner,
+        constraint = owner_token_account.mint == option_mint.key() @ CustomError::InvalidAccount
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
 }
-
 #[derive(Accounts)]
-pub struct Initialize<'info> {
+#[instruction(price: u64, new_price: u64)]
+pub struct RepriceListing<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    pub option_mint: Account<'info, Mint>,
     #[account(
-        init,
-        seeds = [b"auth"],
+        mut,
+        seeds = [b"listing", option_mint.key().as_ref(), signer.key().as_ref(), price.to_be_bytes().as_ref()],
+        bump,
+        close = signer,
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        mut,
+        seeds = [b"listing_vault", listing.key().as_ref()],
         bump,
+    )]
+    pub listing_vault: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
         payer = signer,
-        space = 8
+        seeds = [b"listing", option_mint.key().as_ref(), signer.key().as_ref(), new_price.to_be_bytes().as_ref()],
+        bump,
+        space = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 1 + 8 + 1 + 8 + 8 + 8 + 1
+    )]
+    pub new_listing: Account<'info, Listing>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        seeds = [b"listing_vault", new_listing.key().as_ref()],
+        bump,
+        token::mint = option_mint,
+        token::authority = program_authority,
+    )]
+    pub new_listing_vault: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
     )]
     /// CHECK: 
     pub program_authority: AccountInfo<'info>,
     pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
 }
 #[derive(Accounts)]
-pub struct Create<'info> {
+#[instruction(price: u64)]
+pub struct ReduceListing<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
-    pub underlying_mint: Account<'info, Mint>,
+    pub option_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"listing", option_mint.key().as_ref(), signer.key().as_ref(), price.to_be_bytes().as_ref()],
+        bump,
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        mut,
+        seeds = [b"listing_vault", listing.key().as_ref()],
+        bump,
+    )]
+    pub listing_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = owner_token_account.owner == signer.key() @ CustomError::WrongOwner,
+        constraint = owner_token_account.mint == option_mint.key() @ CustomError::InvalidAccount
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+#[derive(Accounts)]
+pub struct SweepExpiredListings<'info> {
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+#[derive(Accounts)]
+#[instruction(amount: u64, price: u64)]
+pub struct PlaceBid<'info> {
     #[account(mut)]
-    pub user_underlying_token_account: Account<'info, TokenAccount>,
+    pub signer: Signer<'info>,
+    pub option_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump,
+        constraint = option_data_account.resellable @ CustomError::NotResellable,
+    )]
+    pub option_data_account: Account<'info, OptionDataAccount>,
+    #[account(address = option_data_account.quote_mint @ CustomError::InvalidAccount)]
+    pub quote_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    // collects the listing fee on fills
     #[account(
         init_if_needed,
         payer = signer,
-        seeds = [b"underlying_token", underlying_mint.key().as_ref()],
+        seeds = [b"treasury_vault", quote_mint.key().as_ref()],
         bump,
+        token::mint = quote_mint,
         token::authority = program_authority,
-        token::mint = underlying_mint
     )]
-    pub underlying_token_account: Account<'info, TokenAccount>,
+    pub treasury_vault: Account<'info, TokenAccount>,
     #[account(
-        init,
+        init_if_needed,
         payer = signer,
-        mint::authority = program_authority,
-        mint::decimals = OPTION_MINT_DECIMALS,
+        seeds = [b"bid", option_mint.key().as_ref(), signer.key().as_ref(), price.to_be_bytes().as_ref()],
+        bump,
+        space = 8 + 32 + 32 + 32 + 8 + 8
     )]
-    pub option_mint: Account<'info, Mint>,
+    pub bid: Account<'info, Bid>,
+    // holds the quote escrowed by this bid only
     #[account(
-        init,
+        init_if_needed,
         payer = signer,
-        associated_token::mint = option_mint,
-        associated_token::authority = signer,
+        seeds = [b"bid_vault", bid.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = program_authority,
     )]
-    pub user_option_token_account: Account<'info, TokenAccount>,
+    pub bid_vault: Account<'info, TokenAccount>,
     #[account(
-        init,
-        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        seeds = [b"auth"],
         bump,
-        payer = signer,
-        space = 8 + 32 + 32 + 8 + 8 + 8 + 1 + 1,
     )]
-    pub option_data_account: Account<'info, OptionDataAccount>,
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+#[derive(Accounts)]
+#[instruction(price: u64)]
+pub struct CancelBid<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub option_mint: Account<'info, Mint>,
     #[account(
         mut,
+        seeds = [b"bid", option_mint.key().as_ref(), signer.key().as_ref(), price.to_be_bytes().as_ref()],
+        bump,
+        close = signer,
+    )]
+    pub bid: Account<'info, Bid>,
+    #[account(
+        mut,
+        seeds = [b"bid_vault", bid.key().as_ref()],
+        bump,
+    )]
+    pub bid_vault: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
         seeds = [b"auth"],
-        bump
+        bump,
     )]
     /// CHECK: 
     pub program_authority: AccountInfo<'info>,
     pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-}
-#[account]
-pub struct Listing {
-    underlying_mint: Pubkey,
-    option_mint: Pubkey,
-    owner: Pubkey,
-    amount: u64,
-    price: u64,
 }
 #[derive(Accounts)]
-pub struct CreateHolderAccount<'info> {
-    #[account(mut)]
+#[instruction(price: u64)]
+pub struct SellToBid<'info> {
     pub signer: Signer<'info>,
     pub option_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump,
+    )]
+    pub option_data_account: Account<'info, OptionDataAccount>,
+    #[account(mut)]
+    /// CHECK: the bid seeds tie it to the bidder, receives the rent once filled
+    pub bidder: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"bid", option_mint.key().as_ref(), bidder.key().as_ref(), price.to_be_bytes().as_ref()],
+        bump,
+    )]
+    pub bid: Account<'info, Bid>,
+    #[account(
+        mut,
+        seeds = [b"bid_vault", bid.key().as_ref()],
+        bump,
+    )]
+    pub bid_vault: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_option_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_quote_token_account.mint == bid.quote_mint @ CustomError::InvalidAccount
+    )]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = bidder_option_token_account.owner == bidder.key() @ CustomError::InvalidAccount,
+        constraint = bidder_option_token_account.mint == option_mint.key() @ CustomError::InvalidAccount
+    )]
+    pub bidder_option_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = bidder_quote_token_account.owner == bidder.key() @ CustomError::InvalidAccount,
+        constraint = bidder_quote_token_account.mint == bid.quote_mint @ CustomError::InvalidAccount
+    )]
+    pub bidder_quote_token_account: Account<'info, TokenAccount>,
     #[account(
         mut,
+        seeds = [b"treasury_vault", bid.quote_mint.as_ref()],
+        bump,
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
         seeds = [b"auth"],
         bump,
     )]
     /// CHECK: 
     pub program_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+#[derive(Accounts)]
+pub struct CreateOrderBook<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub option_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump,
+        constraint = option_data_account.resellable @ CustomError::NotResellable,
+    )]
+    pub option_data_account: Account<'info, OptionDataAccount>,
+    #[account(address = option_data_account.quote_mint @ CustomError::InvalidAccount)]
+    pub quote_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"order_book", option_mint.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<OrderBook>()
+    )]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    // holds the options of resting asks
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"book_option_vault", order_book.key().as_ref()],
+        bump,
+        token::mint = option_mint,
+        token::authority = program_authority,
+    )]
+    pub book_option_vault: Account<'info, TokenAccount>,
+    // holds the quote of resting bids and of fills owed to ask makers
     #[account(
         init,
         payer = signer,
-        seeds = [b"holder_account", option_mint.key().as_ref()],
+        seeds = [b"book_quote_vault", order_book.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = program_authority,
+    )]
+    pub book_quote_vault: Account<'info, TokenAccount>,
+    // collects the listing fee on fills
+    #[account(
+        init_if_needed,
+        payer = signer,
+        seeds = [b"treasury_vault", quote_mint.key().as_ref()],
         bump,
+        token::mint = quote_mint,
         token::authority = program_authority,
-        token::mint = option_mint
     )]
-    pub program_holder_account: Account<'info, TokenAccount>,
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
 }
 #[derive(Accounts)]
-#[instruction(amount: u64, price: u64)]
-pub struct List<'info> {
-    #[account(mut)]
+pub struct PlaceOrder<'info> {
     pub signer: Signer<'info>,
     pub option_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub user_option_token_account: Account<'info, TokenAccount>,
     #[account(
         seeds = [b"option_data_account", option_mint.key().as_ref()],
-        bump
+        bump,
     )]
     pub option_data_account: Account<'info, OptionDataAccount>,
     #[account(
         mut,
-        seeds = [b"holder_account", option_mint.key().as_ref()],
+        seeds = [b"order_book", option_mint.key().as_ref()],
         bump,
     )]
-    pub program_holder_account: Account<'info, TokenAccount>,
+    pub order_book: AccountLoader<'info, OrderBook>,
     #[account(
-        init_if_needed,
-        payer = signer,
-        seeds = [b"listing", option_mint.key().as_ref(), signer.key().as_ref(), price.to_be_bytes().as_ref()],
+        mut,
+        seeds = [b"book_option_vault", order_book.key().as_ref()],
         bump,
-        space = 8 + 32 + 32 + 32 + 8 + 8
     )]
-    pub list_account: Account<'info, Listing>,
+    pub book_option_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"book_quote_vault", order_book.key().as_ref()],
+        bump,
+    )]
+    pub book_quote_vault: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_option_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"treasury_vault", book_quote_vault.mint.as_ref()],
+        bump,
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
     #[account(
         seeds = [b"auth"],
         bump,
     )]
     /// CHECK: 
     pub program_authority: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
 }
 #[derive(Accounts)]
-#[instruction(price: u64)]
-pub struct Buy<'info> {
-    #[account(mut)]
+pub struct CancelOrder<'info> {
     pub signer: Signer<'info>,
     pub option_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"order_book", option_mint.key().as_ref()],
+        bump,
+    )]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(
+        mut,
+        seeds = [b"book_option_vault", order_book.key().as_ref()],
+        bump,
+    )]
+    pub book_option_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"book_quote_vault", order_book.key().as_ref()],
+        bump,
+    )]
+    pub book_quote_vault: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_option_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
     /// CHECK: 
-    pub owner: AccountInfo<'info>,
+    pub program_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+#[derive(Accounts)]
+pub struct ConsumeEvents<'info> {
+    pub option_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"order_book", option_mint.key().as_ref()],
+        bump,
+    )]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(
+        mut,
+        seeds = [b"book_option_vault", order_book.key().as_ref()],
+        bump,
+    )]
+    pub book_option_vault: Account<'info, TokenAccount>,
     #[account(
         mut,
-        seeds = [b"listing", option_mint.key().as_ref(), owner.key().as_ref(), price.to_be_bytes().as_ref()],
+        seeds = [b"book_quote_vault", order_book.key().as_ref()],
         bump,
     )]
-    pub listing: Account<'info, Listing>,
+    pub book_quote_vault: Account<'info, TokenAccount>,
     #[account(
         mut,
-        seeds = [b"holder_account", option_mint.key().as_ref()],
+        seeds = [b"treasury_vault", book_quote_vault.mint.as_ref()],
         bump,
     )]
-    pub program_holder_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_holder_account: Account<'info, TokenAccount>,
+    pub treasury_vault: Account<'info, TokenAccount>,
     #[account(
         seeds = [b"auth"],
         bump,
     )]
     /// CHECK: 
     pub program_authority: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
 }
 #[derive(Accounts)]
-#[instruction(price: u64)]
-pub struct CloseListing<'info> {
+pub struct Exercise<'info> {
+    #[account(mut)]
     pub signer: Signer<'info>,
-    pub option_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub option_mint: InterfaceAccount<'info, token_interface::Mint>,
     #[account(
         mut,
-        seeds = [b"listing", option_mint.key().as_ref(), owner.key().as_ref(), price.to_be_bytes().as_ref()],
-        bump,
-        close = signer,
+        constraint = user_option_token_account.mint == option_mint.key() @ CustomError::InvalidAccount,
+        constraint = user_option_token_account.owner == signer.key() @ CustomError::WrongOwner
     )]
-    pub listing: Account<'info, Listing>,
+    pub user_option_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
     #[account(
         mut,
-        seeds = [b"holder_account", option_mint.key().as_ref()],
-        bump,
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump
     )]
-    pub program_holder_account: Account<'info, TokenAccount>,
+    pub option_data_account: Account<'info, OptionDataAccount>,
     #[account(
         mut,
-        constraint = owner.key() == listing.owner @ CustomError::InvalidAccount
+        seeds = [b"underlying_vault", option_data_account.key().as_ref()],
+        bump,
     )]
-    /// CHECK: 
-    pub owner: AccountInfo<'info>,
-    /// CHECK: checked in program
-    pub option_data_account: AccountInfo<'info>,
+    pub underlying_token_account: Account<'info, TokenAccount>,
     #[account(
         mut,
-        constraint = owner.key() == owner_token_account.owner
+        seeds = [b"quote_vault", option_data_account.key().as_ref()],
+        bump,
     )]
-    pub owner_token_account: Account<'info, TokenAccount>,
+    pub quote_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_underlying_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    #[account(address = option_data_account.oracle @ CustomError::InvalidAccount)]
+    /// CHECK: checked against the series, read in program
+    pub oracle: AccountInfo<'info>,
     #[account(
+        mut,
         seeds = [b"auth"],
         bump,
     )]
@@ -584,13 +2761,21 @@ pub struct CloseListing<'info> {
     pub program_authority: AccountInfo<'info>,
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
+    #[account(address = *option_mint.to_account_info().owner @ CustomError::InvalidAccount)]
+    pub option_token_program: Interface<'info, TokenInterface>,
 }
+
 #[derive(Accounts)]
-pub struct Exercise<'info> {
+pub struct ClosePosition<'info> {
     pub signer: Signer<'info>,
-    pub option_mint: Account<'info, Mint>,
     #[account(mut)]
-    pub user_option_token_account: Account<'info, TokenAccount>,
+    pub option_mint: InterfaceAccount<'info, token_interface::Mint>,
+    #[account(
+        mut,
+        constraint = user_option_token_account.mint == option_mint.key() @ CustomError::InvalidAccount,
+        constraint = user_option_token_account.owner == signer.key() @ CustomError::WrongOwner
+    )]
+    pub user_option_token_account: InterfaceAccount<'info, token_interface::TokenAccount>,
     #[account(
         mut,
         seeds = [b"option_data_account", option_mint.key().as_ref()],
@@ -599,55 +2784,75 @@ pub struct Exercise<'info> {
     pub option_data_account: Account<'info, OptionDataAccount>,
     #[account(
         mut,
-        seeds = [b"underlying_token", option_data_account.underlying_mint.key().as_ref()],
+        seeds = [b"writer_position", option_data_account.key().as_ref(), signer.key().as_ref()],
         bump,
     )]
-    pub underlying_token_account: Account<'info, TokenAccount>,
+    pub writer_position: Account<'info, WriterPosition>,
     #[account(
         mut,
-        constraint = creator.key() == option_data_account.creator @ CustomError::InvalidAccount
+        seeds = [b"underlying_vault", option_data_account.key().as_ref()],
+        bump,
     )]
-    /// CHECK: 
-    pub creator: AccountInfo<'info>,
+    pub underlying_token_account: Account<'info, TokenAccount>,
     #[account(
         mut,
-        constraint = creator.key() == creator_token_account.owner @ CustomError::InvalidAccount
+        seeds = [b"quote_vault", option_data_account.key().as_ref()],
+        bump,
     )]
-    pub creator_token_account: Account<'info, TokenAccount>,
+    pub quote_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
     pub user_underlying_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
     #[account(
-        mut,
         seeds = [b"auth"],
         bump,
     )]
     /// CHECK: 
     pub program_authority: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
+    #[account(address = *option_mint.to_account_info().owner @ CustomError::InvalidAccount)]
+    pub option_token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
 pub struct Claim<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    #[account(address = option_data_account.underlying_mint @ CustomError::InvalidAccount)]
     pub underlying_mint: Account<'info, Mint>,
-    pub option_mint: Account<'info, Mint>,
+    #[account(address = option_data_account.quote_mint @ CustomError::InvalidAccount)]
+    pub quote_mint: Account<'info, Mint>,
+    pub option_mint: InterfaceAccount<'info, token_interface::Mint>,
     #[account(
         mut,
         seeds = [b"option_data_account", option_mint.key().as_ref()],
         bump,
-        close = signer,
     )]
     pub option_data_account: Account<'info, OptionDataAccount>,
     #[account(
         mut,
-        seeds = [b"underlying_token", underlying_mint.key().as_ref()],
+        seeds = [b"writer_position", option_data_account.key().as_ref(), signer.key().as_ref()],
+        bump,
+        close = signer,
+    )]
+    pub writer_position: Account<'info, WriterPosition>,
+    #[account(
+        mut,
+        seeds = [b"underlying_vault", option_data_account.key().as_ref()],
         bump,
     )]
-    pub program_holder_account: Account<'info, TokenAccount>,
+    pub underlying_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
     pub user_underlying_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"quote_vault", option_data_account.key().as_ref()],
+        bump,
+    )]
+    pub quote_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: Account<'info, TokenAccount>,
     #[account(
         mut,
         seeds = [b"auth"],
@@ -660,10 +2865,98 @@ pub struct Claim<'info> {
 }
 #[account]
 pub struct Pool {
+    // created the pool and may close it
+    owner: Pubkey,
     option_mint: Pubkey,
+    quote_mint: Pubkey,
+    // quote base units per contract the pool opened at
     base_price: u64,
-    right: u64,
-    left: u64,
+    // option base units held by the pool
+    option_reserve: u64,
+    // quote base units held by the pool
+    quote_reserve: u64,
+    // quote depth the pool was opened with, priced but never paid out
+    virtual_quote_reserve: u64,
+    // option inventory the lps deposited, model prices skew as the reserve moves away from it
+    option_target: u64,
+    curve: PoolCurve,
+    // spot feed of model priced pools, the default pubkey otherwise
+    oracle: Pubkey,
+    // marginal price after the latest swap
+    price: u64,
+    // sum of the marginal price times the seconds it held since the first swap, accumulated up to
+    // `price_timestamp`. consumers may snapshot it themselves for their own windows
+    price_cumulative: u128,
+    // time of the latest swap, zero before the first one
+    price_timestamp: u64,
+    // slot of the latest entry in `observations`
+    observation_index: u8,
+    // ring of `price_cumulative` snapshots taken by swaps, empty entries have a zero timestamp
+    observations: [Observation; POOL_OBSERVATIONS],
+}
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct Observation {
+    timestamp: u64,
+    price_cumulative: u128,
+}
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PoolCurve {
+    // x * y = k between the option and quote reserves
+    ConstantProduct,
+    // black-scholes value at `volatility_bps` annualized implied volatility, each contract
+    // marked up to `skew_bps` as the pool sells down its inventory and down as it buys more
+    BlackScholes { volatility_bps: u64, skew_bps: u64 },
+}
+impl Pool {
+    // quote side of the constant product
+    fn quote_depth(&self) -> Result<u64> {
+        checked_add(self.quote_reserve, self.virtual_quote_reserve)
+    }
+    // accumulates the previous price up to `now` and records `price` as holding from then on.
+    // a snapshot is taken once the latest one is an interval old
+    fn observe(&mut self, now: u64, price: u64) -> Result<()> {
+        if self.price_timestamp != 0 {
+            self.price_cumulative = self.cumulative_at(now)?;
+        }
+        self.price = price;
+        self.price_timestamp = now;
+        let latest = self.observations[self.observation_index as usize];
+        if latest.timestamp == 0 || now >= latest.timestamp + OBSERVATION_INTERVAL_SECONDS {
+            self.observation_index = ((self.observation_index as usize + 1) % POOL_OBSERVATIONS) as u8;
+            self.observations[self.observation_index as usize] = Observation { timestamp: now, price_cumulative: self.price_cumulative };
+        }
+        Ok(())
+    }
+    // cumulative price at `time`, no earlier than the latest swap
+    fn cumulative_at(&self, time: u64) -> Result<u128> {
+        match (self.price as u128).checked_mul((time - self.price_timestamp) as u128).and_then(|num| num.checked_add(self.price_cumulative)) {
+            None => Err(CustomError::MathOverflow.into()),
+            Some(num) => Ok(num),
+        }
+    }
+    fn twap(&self, now: u64, window: u64) -> Result<u64> {
+        if self.price_timestamp == 0 {
+            return Err(CustomError::InsufficientPriceHistory.into())
+        }
+        if window == 0 {
+            return Ok(self.price)
+        }
+        let start = match now.checked_sub(window) {
+            None => return Err(CustomError::InsufficientPriceHistory.into()),
+            Some(num) => num,
+        };
+        let first = match self.observations.iter()
+            .filter(|observation| observation.timestamp != 0 && observation.timestamp <= start)
+            .max_by_key(|observation| observation.timestamp) {
+            None => return Err(CustomError::InsufficientPriceHistory.into()),
+            Some(observation) => observation,
+        };
+        let total = self.cumulative_at(now)? - first.price_cumulative;
+        match u64::try_from(total / (now - first.timestamp) as u128) {
+            Err(_) => Err(CustomError::MathOverflow.into()),
+            Ok(num) => Ok(num),
+        }
+    }
 }
 #[derive(Accounts)]
 #[instruction(base_price: u64)]
@@ -673,23 +2966,126 @@ pub struct CreatePool<'info> {
     #[account(mut)]
     pub user_option_account: Account<'info, TokenAccount>,
     pub option_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump,
+        constraint = option_data_account.resellable @ CustomError::NotResellable,
+    )]
+    pub option_data_account: Account<'info, OptionDataAccount>,
+    #[account(address = option_data_account.quote_mint @ CustomError::InvalidAccount)]
+    pub quote_mint: Account<'info, Mint>,
     #[account(
         init,
         payer = signer,
         seeds = [b"pool", option_mint.key().as_ref(), base_price.to_be_bytes().as_ref()],
         bump,
-        space = 32 + 8 + 8 + 8,
+        space = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 32 + 8 + 16 + 8 + 1 + (8 + 16) * POOL_OBSERVATIONS,
     )]
     pub pool: Account<'info, Pool>,
+    /// CHECK: spot feed of model priced pools, read in program
+    pub oracle: AccountInfo<'info>,
+    // reserves of this pool only
     #[account(
-        init_if_needed,
+        init,
+        payer = signer,
+        seeds = [b"pool_quote_vault", pool.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = program_authority,
+    )]
+    pub pool_quote_vault: Account<'info, TokenAccount>,
+    #[account(
+        init,
         payer = signer,
-        seeds = [b"holder_account", option_mint.key().as_ref()],
+        seeds = [b"pool_option_vault", pool.key().as_ref()],
         bump,
         token::mint = option_mint,
         token::authority = program_authority,
     )]
-    pub program_holder_account: Account<'info, TokenAccount>,
+    pub pool_option_vault: Account<'info, TokenAccount>,
+    // collects the protocol share of swap fees
+    #[account(
+        init_if_needed,
+        payer = signer,
+        seeds = [b"treasury_vault", quote_mint.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = program_authority,
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"lp_mint", pool.key().as_ref()],
+        bump,
+        mint::authority = program_authority,
+        mint::decimals = OPTION_MINT_DECIMALS,
+    )]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = lp_mint,
+        associated_token::authority = signer,
+    )]
+    pub user_lp_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(base_price: u64)]
+pub struct AddLiquidity<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    pub option_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump,
+    )]
+    pub option_data_account: Account<'info, OptionDataAccount>,
+    #[account(
+        mut,
+        seeds = [b"pool", option_mint.key().as_ref(), base_price.to_be_bytes().as_ref()],
+        bump,
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        seeds = [b"lp_mint", pool.key().as_ref()],
+        bump,
+    )]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = lp_mint,
+        associated_token::authority = signer,
+    )]
+    pub user_lp_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_option_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"pool_option_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_option_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"pool_quote_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_quote_vault: Account<'info, TokenAccount>,
     #[account(
         seeds = [b"auth"],
         bump,
@@ -697,7 +3093,56 @@ pub struct CreatePool<'info> {
     /// CHECK: 
     pub program_authority: AccountInfo<'info>,
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(base_price: u64)]
+pub struct RemoveLiquidity<'info> {
+    pub signer: Signer<'info>,
+    pub option_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"pool", option_mint.key().as_ref(), base_price.to_be_bytes().as_ref()],
+        bump,
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        seeds = [b"lp_mint", pool.key().as_ref()],
+        bump,
+    )]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = lp_mint,
+        token::authority = signer,
+    )]
+    pub user_lp_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_option_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"pool_option_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_option_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"pool_quote_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_quote_vault: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
@@ -707,14 +3152,41 @@ pub struct SwapPool<'info> {
     #[account(mut)]
     pub user_option_token_account: Account<'info, TokenAccount>,
     pub option_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump,
+        constraint = option_data_account.resellable @ CustomError::NotResellable,
+    )]
+    pub option_data_account: Account<'info, OptionDataAccount>,
     #[account(
         mut,
         seeds = [b"pool", option_mint.key().as_ref(), base_price.to_be_bytes().as_ref()],
         bump,
     )]
     pub pool: Account<'info, Pool>,
+    #[account(address = pool.oracle @ CustomError::InvalidAccount)]
+    /// CHECK: checked against the pool, read in program
+    pub oracle: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"pool_option_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_option_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"pool_quote_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_quote_vault: Account<'info, TokenAccount>,
     #[account(mut)]
-    pub program_holder_account: Account<'info, TokenAccount>,
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"treasury_vault", pool.quote_mint.key().as_ref()],
+        bump,
+    )]
+    pub treasury_vault: Account<'info, TokenAccount>,
     #[account(
         mut,
         seeds = [b"auth"],
@@ -725,6 +3197,16 @@ pub struct SwapPool<'info> {
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
 }
+#[derive(Accounts)]
+#[instruction(base_price: u64)]
+pub struct PoolTwap<'info> {
+    pub option_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [b"pool", option_mint.key().as_ref(), base_price.to_be_bytes().as_ref()],
+        bump,
+    )]
+    pub pool: Account<'info, Pool>,
+}
 
 #[derive(Accounts)]
 #[instruction(base_price: u64)]
@@ -732,15 +3214,62 @@ pub struct ClosePool<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
     pub option_mint: Account<'info, Mint>,
-    /// CHECK: checked in program
-    pub option_data_account: AccountInfo<'info>,
+    #[account(
+        seeds = [b"option_data_account", option_mint.key().as_ref()],
+        bump
+    )]
+    pub option_data_account: Account<'info, OptionDataAccount>,
     #[account(
         mut,
         seeds = [b"pool", option_mint.key().as_ref(), base_price.to_be_bytes().as_ref()],
         bump,
+        constraint = pool.owner == signer.key() @ CustomError::WrongOwner,
         close = signer,
     )]
     pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        seeds = [b"lp_mint", pool.key().as_ref()],
+        bump,
+    )]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = lp_mint,
+        token::authority = signer,
+    )]
+    pub user_lp_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"pool_option_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_option_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"pool_quote_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_quote_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = option_mint,
+        token::authority = signer,
+    )]
+    pub user_option_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = pool.quote_mint,
+        token::authority = signer,
+    )]
+    pub user_quote_token_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"auth"],
+        bump,
+    )]
+    /// CHECK: 
+    pub program_authority: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
 }
 
 
@@ -0,0 +1,125 @@
+/*
+    fixed point conversions between option, underlying and quote units.
+
+    option amounts are in option base units, `price_scale` of them make one contract.
+    a series' `contract_multiplier` is the underlying base units delivered per contract,
+    and every price (strike, listing, pool) is quote base units per contract.
+    intermediates are u128 and every rounding favors the protocol: amounts paid in
+    round up, amounts paid out round down.
+*/
+use anchor_lang::prelude::*;
+use crate::{CustomError, OptionDataAccount};
+
+#[derive(Clone, Copy)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+pub fn mul_div(a: u64, b: u64, denominator: u64, rounding: Rounding) -> Result<u64> {
+    if denominator == 0 {
+        return Err(CustomError::MathOverflow.into())
+    }
+    let numerator = a as u128 * b as u128;
+    let denominator = denominator as u128;
+    let quotient = match rounding {
+        Rounding::Down => numerator / denominator,
+        Rounding::Up => numerator.div_ceil(denominator),
+    };
+    match u64::try_from(quotient) {
+        Err(_) => Err(CustomError::MathOverflow.into()),
+        Ok(num) => Ok(num),
+    }
+}
+
+pub fn checked_add(a: u64, b: u64) -> Result<u64> {
+    match a.checked_add(b) {
+        None => Err(CustomError::MathOverflow.into()),
+        Some(num) => Ok(num),
+    }
+}
+
+pub fn checked_sub(a: u64, b: u64) -> Result<u64> {
+    match a.checked_sub(b) {
+        None => Err(CustomError::MathOverflow.into()),
+        Some(num) => Ok(num),
+    }
+}
+
+// quote owed for `amount` option units at `price` quote per contract
+pub fn quote_for_options(amount: u64, price: u64, price_scale: u64, rounding: Rounding) -> Result<u64> {
+    mul_div(amount, price, price_scale, rounding)
+}
+
+// underlying delivered against `amount` option units of the series
+pub fn underlying_for_options(series: &OptionDataAccount, amount: u64, rounding: Rounding) -> Result<u64> {
+    mul_div(amount, series.contract_multiplier, series.price_scale, rounding)
+}
+
+// strike owed against `amount` option units of the series
+pub fn strike_for_options(series: &OptionDataAccount, amount: u64, rounding: Rounding) -> Result<u64> {
+    quote_for_options(amount, series.strike_price, series.price_scale, rounding)
+}
+
+// collateral backing `amount` option units: underlying for calls, strike in quote for puts
+pub fn collateral_for_options(series: &OptionDataAccount, amount: u64, rounding: Rounding) -> Result<u64> {
+    if series.collateral_in_underlying() {
+        underlying_for_options(series, amount, rounding)
+    } else {
+        strike_for_options(series, amount, rounding)
+    }
+}
+
+// oracle spot, in quote whole tokens per underlying whole token scaled by 10^expo,
+// converted to quote base units per contract of the series
+pub fn spot_per_contract(series: &OptionDataAccount, price: u64, expo: i32) -> Result<u64> {
+    let exponent = expo + series.quote_decimals as i32 - series.underlying_decimals as i32;
+    let scale = match 10u128.checked_pow(exponent.unsigned_abs()) {
+        None => return Err(CustomError::MathOverflow.into()),
+        Some(num) => num,
+    };
+    let value = price as u128 * series.contract_multiplier as u128;
+    let value = if exponent >= 0 { value.checked_mul(scale) } else { Some(value / scale) };
+    match value.map(u64::try_from) {
+        Some(Ok(num)) => Ok(num),
+        _ => Err(CustomError::MathOverflow.into()),
+    }
+}
+
+// cash settlement value per contract in quote base units
+pub fn intrinsic_per_contract(series: &OptionDataAccount, spot: u64) -> u64 {
+    if series.call {
+        spot.saturating_sub(series.strike_price)
+    } else {
+        series.strike_price.saturating_sub(spot)
+    }
+}
+
+// cash settlement paid against `amount` option units, out of their collateral:
+// `intrinsic` per contract in quote for puts, underlying worth it at `spot` for calls
+pub fn settlement_for_options(series: &OptionDataAccount, amount: u64, intrinsic: u64, spot: u64, rounding: Rounding) -> Result<u64> {
+    if series.call {
+        mul_div(underlying_for_options(series, amount, rounding)?, intrinsic, spot, rounding)
+    } else {
+        quote_for_options(amount, intrinsic, series.price_scale, rounding)
+    }
+}
+
+// constant product quote paid for `amount` option units taken out of the pool,
+// integrated over the trade so the marginal price rises with size
+pub fn quote_in_for_options(option_reserve: u64, quote_reserve: u64, amount: u64) -> Result<u64> {
+    if amount >= option_reserve {
+        return Err(CustomError::PoolEmpty.into())
+    }
+    mul_div(quote_reserve, amount, option_reserve - amount, Rounding::Up)
+}
+
+// constant product quote paid out for `amount` option units sold into the pool
+pub fn quote_out_for_options(option_reserve: u64, quote_reserve: u64, amount: u64) -> Result<u64> {
+    mul_div(quote_reserve, amount, checked_add(option_reserve, amount)?, Rounding::Down)
+}
+
+// fee of `basis_points` on `amount`, rounded up
+pub fn fee_for(amount: u64, basis_points: u64) -> Result<u64> {
+    mul_div(amount, basis_points, 10_000, Rounding::Up)
+}
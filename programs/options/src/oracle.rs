@@ -0,0 +1,184 @@
+/*
+    price feeds read by cash settled series.
+    a price is quote whole tokens per whole underlying token, scaled by 10^expo.
+    the feed layout is picked from the account owner:
+    pyth legacy price accounts, switchboard v2 aggregators, or a program owned `MockOracle`
+    whose authority sets the price. mock feeds are only read in builds with the `mock-oracle`
+    feature, for localnet tests.
+    every read is rejected if the price is older than `MAX_ORACLE_AGE_SECONDS`
+    or its confidence interval is wider than `MAX_CONFIDENCE_BASIS_POINTS` of the price.
+*/
+use anchor_lang::prelude::*;
+use crate::CustomError;
+
+mod pyth {
+    anchor_lang::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+}
+mod pyth_devnet {
+    anchor_lang::declare_id!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
+}
+mod switchboard {
+    anchor_lang::declare_id!("SW1TCH7qEPTdLsDHRgPuMQjbQxKdH2aBStViMFnt64f");
+}
+pub const MAX_ORACLE_AGE_SECONDS: u64 = 60;
+pub const MAX_CONFIDENCE_BASIS_POINTS: u64 = 200;
+
+#[account]
+pub struct MockOracle {
+    pub authority: Pubkey,
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+pub struct OraclePrice {
+    pub price: u64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+// a price account layout this program can read
+trait PriceFeed {
+    fn decode(data: &[u8]) -> Result<OraclePrice>;
+}
+
+struct PythFeed;
+struct SwitchboardFeed;
+
+// pyth legacy `PriceAccount`, only the aggregate price is read
+impl PythFeed {
+    const MAGIC: u32 = 0xa1b2c3d4;
+    const VERSION: u32 = 2;
+    const PRICE_ACCOUNT_TYPE: u32 = 3;
+    const STATUS_TRADING: u32 = 1;
+}
+impl PriceFeed for PythFeed {
+    fn decode(data: &[u8]) -> Result<OraclePrice> {
+        if data.len() < 240
+            || read_u32(data, 0)? != Self::MAGIC
+            || read_u32(data, 4)? != Self::VERSION
+            || read_u32(data, 8)? != Self::PRICE_ACCOUNT_TYPE {
+            return Err(CustomError::InvalidAccount.into())
+        }
+        if read_u32(data, 224)? != Self::STATUS_TRADING {
+            return Err(CustomError::TokenPriceNotFound.into())
+        }
+        Ok(OraclePrice {
+            price: positive(read_i64(data, 208)? as i128)?,
+            conf: read_u64(data, 216)?,
+            expo: read_i32(data, 20)?,
+            publish_time: read_i64(data, 96)?,
+        })
+    }
+}
+
+// switchboard v2 `AggregatorAccountData`, read from its latest confirmed round
+impl SwitchboardFeed {
+    const DISCRIMINATOR: [u8; 8] = [217, 230, 65, 101, 201, 162, 27, 125];
+    const ROUND: usize = 341;
+}
+impl PriceFeed for SwitchboardFeed {
+    fn decode(data: &[u8]) -> Result<OraclePrice> {
+        if data.len() < Self::ROUND + 65 || data[..8] != Self::DISCRIMINATOR {
+            return Err(CustomError::InvalidAccount.into())
+        }
+        if read_u32(data, Self::ROUND)? == 0 {
+            return Err(CustomError::TokenPriceNotFound.into())
+        }
+        // decimals are mantissa * 10^-scale, the deviation is rescaled to the result's scale
+        let scale = read_u32(data, Self::ROUND + 41)?;
+        let deviation_scale = read_u32(data, Self::ROUND + 61)?;
+        let deviation = read_i128(data, Self::ROUND + 45)?.unsigned_abs();
+        let conf = if deviation_scale >= scale {
+            deviation / 10u128.pow((deviation_scale - scale).min(38))
+        } else {
+            match 10u128.checked_pow(scale - deviation_scale).and_then(|factor| deviation.checked_mul(factor)) {
+                None => return Err(CustomError::MathOverflow.into()),
+                Some(num) => num,
+            }
+        };
+        Ok(OraclePrice {
+            price: positive(read_i128(data, Self::ROUND + 25)?)?,
+            conf: match u64::try_from(conf) {
+                Err(_) => return Err(CustomError::MathOverflow.into()),
+                Ok(num) => num,
+            },
+            expo: match i32::try_from(scale) {
+                Err(_) => return Err(CustomError::MathOverflow.into()),
+                Ok(num) => -num,
+            },
+            publish_time: read_i64(data, Self::ROUND + 17)?,
+        })
+    }
+}
+
+impl PriceFeed for MockOracle {
+    fn decode(data: &[u8]) -> Result<OraclePrice> {
+        let feed = MockOracle::try_deserialize(&mut &data[..])?;
+        Ok(OraclePrice {
+            price: positive(feed.price as i128)?,
+            conf: feed.conf,
+            expo: feed.expo,
+            publish_time: feed.publish_time,
+        })
+    }
+}
+
+// decodes the feed without checking its age, used to validate an oracle when a series is written
+pub fn decode_price(oracle: &AccountInfo) -> Result<OraclePrice> {
+    let data = oracle.data.borrow();
+    if oracle.owner == &pyth::ID || oracle.owner == &pyth_devnet::ID {
+        PythFeed::decode(&data)
+    } else if oracle.owner == &switchboard::ID {
+        SwitchboardFeed::decode(&data)
+    } else if cfg!(feature = "mock-oracle") && oracle.owner == &crate::ID {
+        MockOracle::decode(&data)
+    } else {
+        Err(CustomError::InvalidAccount.into())
+    }
+}
+
+pub fn read_price(oracle: &AccountInfo, now: u64) -> Result<OraclePrice> {
+    let price = decode_price(oracle)?;
+    if price.publish_time < 0 || now.saturating_sub(price.publish_time as u64) > MAX_ORACLE_AGE_SECONDS {
+        return Err(CustomError::StalePrice.into())
+    }
+    if price.conf as u128 * 10_000 > price.price as u128 * MAX_CONFIDENCE_BASIS_POINTS as u128 {
+        return Err(CustomError::PriceConfidenceTooWide.into())
+    }
+    Ok(price)
+}
+
+fn positive(price: i128) -> Result<u64> {
+    if price <= 0 {
+        return Err(CustomError::TokenPriceNotFound.into())
+    }
+    match u64::try_from(price) {
+        Err(_) => Err(CustomError::MathOverflow.into()),
+        Ok(num) => Ok(num),
+    }
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    match data.get(offset..offset + N) {
+        None => Err(CustomError::InvalidAccount.into()),
+        Some(bytes) => Ok(bytes.try_into().unwrap()),
+    }
+}
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(data, offset)?))
+}
+fn read_i32(data: &[u8], offset: usize) -> Result<i32> {
+    Ok(i32::from_le_bytes(read_bytes(data, offset)?))
+}
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(data, offset)?))
+}
+fn read_i64(data: &[u8], offset: usize) -> Result<i64> {
+    Ok(i64::from_le_bytes(read_bytes(data, offset)?))
+}
+fn read_i128(data: &[u8], offset: usize) -> Result<i128> {
+    Ok(i128::from_le_bytes(read_bytes(data, offset)?))
+}
@@ -0,0 +1,209 @@
+/*
+    central limit order book of one option series.
+    resting orders are kept best price first and in placement order within a price,
+    so takers match them in price-time priority. asks escrow their options and bids their quote
+    in the book's vaults. takers settle in the same instruction, makers are paid out of the vaults
+    when their fills are consumed from the event queue.
+    a full side evicts its worst priced order for a better one, the evicted order's escrow
+    is returned through the event queue like a fill.
+*/
+use anchor_lang::prelude::*;
+use crate::CustomError;
+use crate::math::{checked_add, quote_for_options, Rounding};
+
+pub const MAX_ORDERS: usize = 32;
+pub const MAX_EVENTS: usize = 64;
+// option base units an order that may rest on the book must be for, a hundredth of a contract
+pub const MIN_ORDER_AMOUNT: u64 = 10_000;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+#[zero_copy]
+pub struct Order {
+    pub owner: Pubkey,
+    // quote base units per contract
+    pub price: u64,
+    // option units still resting
+    pub amount: u64,
+    // placement number, identifies the order
+    pub sequence: u64,
+}
+
+#[zero_copy]
+pub struct FillEvent {
+    pub maker: Pubkey,
+    pub price: u64,
+    pub amount: u64,
+    // quote traded, paid to ask makers or already paid out of bid makers' escrow.
+    // for evicted bids the escrow returned to the maker
+    pub quote: u64,
+    // 0 when the maker was a bid, 1 when it was an ask
+    pub maker_side: u8,
+    // 1 when the maker's order was evicted from a full side instead of filled
+    pub evicted: u8,
+    pub padding: [u8; 6],
+}
+
+#[account(zero_copy)]
+pub struct OrderBook {
+    pub option_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub next_sequence: u64,
+    pub bid_count: u64,
+    pub ask_count: u64,
+    // ring position of the oldest unconsumed fill, and how many are queued
+    pub event_head: u64,
+    pub event_count: u64,
+    pub bids: [Order; MAX_ORDERS],
+    pub asks: [Order; MAX_ORDERS],
+    pub events: [FillEvent; MAX_EVENTS],
+}
+
+// option units and quote a taker traded against resting orders
+pub struct Fill {
+    pub amount: u64,
+    pub quote: u64,
+}
+
+impl FillEvent {
+    pub fn maker_side(&self) -> Side {
+        if self.maker_side == 0 { Side::Bid } else { Side::Ask }
+    }
+
+    pub fn is_evicted(&self) -> bool {
+        self.evicted != 0
+    }
+}
+
+impl OrderBook {
+    fn side_mut(&mut self, side: Side) -> (&mut [Order; MAX_ORDERS], &mut u64) {
+        match side {
+            Side::Bid => (&mut self.bids, &mut self.bid_count),
+            Side::Ask => (&mut self.asks, &mut self.ask_count),
+        }
+    }
+
+    // matches a taker against the opposite side up to `price`, at each maker's price.
+    // buyers pay each fill rounded up and sellers receive it rounded down
+    pub fn match_order(&mut self, side: Side, price: u64, amount: u64, price_scale: u64) -> Result<Fill> {
+        let maker_side = match side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+        let rounding = match side {
+            Side::Bid => Rounding::Up,
+            Side::Ask => Rounding::Down,
+        };
+        let mut fill = Fill { amount: 0, quote: 0 };
+        while fill.amount < amount {
+            let (orders, count) = self.side_mut(maker_side);
+            if *count == 0 {
+                break
+            }
+            let best = orders[0];
+            let crosses = match side {
+                Side::Bid => best.price <= price,
+                Side::Ask => best.price >= price,
+            };
+            if !crosses {
+                break
+            }
+            let traded = best.amount.min(amount - fill.amount);
+            let quote = quote_for_options(traded, best.price, price_scale, rounding)?;
+            if traded == best.amount {
+                orders.copy_within(1..*count as usize, 0);
+                *count -= 1;
+            } else {
+                orders[0].amount -= traded;
+            }
+            self.push_event(FillEvent {
+                maker: best.owner,
+                price: best.price,
+                amount: traded,
+                quote,
+                maker_side: maker_side as u8,
+                evicted: 0,
+                padding: [0; 6],
+            })?;
+            fill.amount += traded;
+            fill.quote = checked_add(fill.quote, quote)?;
+        }
+        Ok(fill)
+    }
+
+    // rests an order behind every order at the same or a better price, returns its sequence.
+    // on a full side it must be priced better than the worst order, which is evicted
+    pub fn insert(&mut self, side: Side, owner: Pubkey, price: u64, amount: u64, price_scale: u64) -> Result<u64> {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        let (orders, count) = self.side_mut(side);
+        let position = orders[..*count as usize].iter().position(|order| match side {
+            Side::Bid => order.price < price,
+            Side::Ask => order.price > price,
+        }).unwrap_or(*count as usize);
+        if *count as usize == MAX_ORDERS {
+            if position == MAX_ORDERS {
+                return Err(CustomError::OrderBookFull.into())
+            }
+            let worst = orders[MAX_ORDERS - 1];
+            *count -= 1;
+            // bids escrowed their quote rounded up, asks their options
+            let quote = match side {
+                Side::Bid => quote_for_options(worst.amount, worst.price, price_scale, Rounding::Up)?,
+                Side::Ask => 0,
+            };
+            self.push_event(FillEvent {
+                maker: worst.owner,
+                price: worst.price,
+                amount: worst.amount,
+                quote,
+                maker_side: side as u8,
+                evicted: 1,
+                padding: [0; 6],
+            })?;
+        }
+        let (orders, count) = self.side_mut(side);
+        let len = *count as usize;
+        orders.copy_within(position..len, position + 1);
+        orders[position] = Order { owner, price, amount, sequence };
+        *count += 1;
+        Ok(sequence)
+    }
+
+    pub fn remove(&mut self, side: Side, sequence: u64) -> Result<Order> {
+        let (orders, count) = self.side_mut(side);
+        let len = *count as usize;
+        let position = match orders[..len].iter().position(|order| order.sequence == sequence) {
+            None => return Err(CustomError::OrderNotFound.into()),
+            Some(num) => num,
+        };
+        let order = orders[position];
+        orders.copy_within(position + 1..len, position);
+        *count -= 1;
+        Ok(order)
+    }
+
+    fn push_event(&mut self, event: FillEvent) -> Result<()> {
+        if self.event_count as usize == MAX_EVENTS {
+            return Err(CustomError::EventQueueFull.into())
+        }
+        let slot = (self.event_head + self.event_count) as usize % MAX_EVENTS;
+        self.events[slot] = event;
+        self.event_count += 1;
+        Ok(())
+    }
+
+    pub fn pop_event(&mut self) -> Option<FillEvent> {
+        if self.event_count == 0 {
+            return None
+        }
+        let event = self.events[self.event_head as usize];
+        self.event_head = (self.event_head + 1) % MAX_EVENTS as u64;
+        self.event_count -= 1;
+        Some(event)
+    }
+}
@@ -0,0 +1,211 @@
+/*
+    black-scholes pricing for option aware pools.
+    the model runs in signed fixed point with `WAD` = 10^12 and a zero interest rate.
+    spot and strike are quote base units per contract, so model values come out in the same unit.
+    the normal cdf uses the abramowitz-stegun 26.2.17 approximation (error below 10^-7).
+*/
+use anchor_lang::prelude::*;
+use crate::{CustomError, OptionDataAccount};
+use crate::math::{mul_div, Rounding};
+
+const WAD: i128 = 1_000_000_000_000;
+const LN_2: i128 = 693_147_180_560;
+const INV_SQRT_2PI: i128 = 398_942_280_401;
+const SECONDS_PER_YEAR: i128 = 365 * 24 * 60 * 60;
+const CDF_P: i128 = 231_641_900_000;
+const CDF_B: [i128; 5] = [319_381_530_000, -356_563_782_000, 1_781_477_937_000, -1_821_255_978_000, 1_330_274_429_000];
+// highest annualized volatility a model priced pool may quote at, 1000%
+pub const MAX_VOLATILITY_BPS: u64 = 100_000;
+// most an exponential dutch auction may fall from its start price, below it the end to start ratio
+// rounds to zero in fixed point
+pub const MAX_DECAY_RATIO: u128 = WAD as u128;
+
+fn overflow() -> Error {
+    CustomError::MathOverflow.into()
+}
+
+// a * b / c for model intermediates
+fn checked_mul_div(a: i128, b: i128, c: i128) -> Result<i128> {
+    match a.checked_mul(b).and_then(|num| num.checked_div(c)) {
+        None => Err(overflow()),
+        Some(num) => Ok(num),
+    }
+}
+
+fn ln(x: i128) -> Result<i128> {
+    if x <= 0 {
+        return Err(overflow())
+    }
+    // x = m * 2^k with m in [1, 2), ln(m) = 2 atanh((m - 1) / (m + 1))
+    let mut m = x;
+    let mut k = 0;
+    while m >= 2 * WAD {
+        m /= 2;
+        k += 1;
+    }
+    while m < WAD {
+        m *= 2;
+        k -= 1;
+    }
+    let z = (m - WAD) * WAD / (m + WAD);
+    let z2 = z * z / WAD;
+    let mut term = z;
+    let mut sum = 0;
+    let mut n = 1;
+    while term != 0 {
+        sum += term / n;
+        term = term * z2 / WAD;
+        n += 2;
+    }
+    Ok(2 * sum + k * LN_2)
+}
+
+fn exp(x: i128) -> Result<i128> {
+    if x < -40 * WAD {
+        return Ok(0)
+    }
+    if x > 40 * WAD {
+        return Err(overflow())
+    }
+    // x = k ln 2 + r with r in [0, ln 2)
+    let k = x.div_euclid(LN_2);
+    let r = x - k * LN_2;
+    let mut term = WAD;
+    let mut sum = WAD;
+    let mut n = 1;
+    while term != 0 {
+        term = term * r / (WAD * n);
+        sum += term;
+        n += 1;
+    }
+    Ok(if k >= 0 { sum << k } else { sum >> -k })
+}
+
+fn sqrt(x: i128) -> Result<i128> {
+    if x < 0 {
+        return Err(overflow())
+    }
+    let value = match (x as u128).checked_mul(WAD as u128) {
+        None => return Err(overflow()),
+        Some(num) => num,
+    };
+    if value == 0 {
+        return Ok(0)
+    }
+    let mut root = value;
+    let mut next = root.div_ceil(2);
+    while next < root {
+        root = next;
+        next = (root + value / root) / 2;
+    }
+    Ok(root as i128)
+}
+
+fn norm_cdf(x: i128) -> Result<i128> {
+    if x < 0 {
+        return Ok(WAD - norm_cdf(-x)?)
+    }
+    if x > 10 * WAD {
+        return Ok(WAD)
+    }
+    let t = WAD * WAD / (WAD + CDF_P * x / WAD);
+    let mut poly = 0;
+    for b in CDF_B.iter().rev() {
+        poly = (poly + b) * t / WAD;
+    }
+    let pdf = INV_SQRT_2PI * exp(-(x * x / WAD) / 2)? / WAD;
+    Ok(WAD - pdf * poly / WAD)
+}
+
+// call value with volatility `sigma_sqrt_t` over the remaining life, never below zero
+fn call_value(spot: i128, strike: i128, sigma_sqrt_t: i128) -> Result<i128> {
+    if spot == 0 {
+        return Ok(0)
+    }
+    if sigma_sqrt_t == 0 {
+        return Ok((spot - strike).max(0))
+    }
+    let variance = checked_mul_div(sigma_sqrt_t, sigma_sqrt_t, 2 * WAD)?;
+    let log_moneyness = ln(checked_mul_div(spot, WAD, strike)?)?;
+    let d1 = match log_moneyness.checked_add(variance) {
+        None => return Err(overflow()),
+        Some(num) => checked_mul_div(num, WAD, sigma_sqrt_t)?,
+    };
+    let d2 = match d1.checked_sub(sigma_sqrt_t) {
+        None => return Err(overflow()),
+        Some(num) => num,
+    };
+    let value = checked_mul_div(spot, norm_cdf(d1)?, WAD)? - checked_mul_div(strike, norm_cdf(d2)?, WAD)?;
+    Ok(value.max(0))
+}
+
+// model value per contract of the series, in quote base units
+pub fn fair_value(series: &OptionDataAccount, spot: u64, seconds_to_expiry: u64, volatility_bps: u64) -> Result<u64> {
+    let spot = spot as i128;
+    let strike = series.strike_price as i128;
+    if strike == 0 {
+        return Err(overflow())
+    }
+    let years = checked_mul_div(seconds_to_expiry as i128, WAD, SECONDS_PER_YEAR)?;
+    let sigma_sqrt_t = checked_mul_div(volatility_bps as i128, sqrt(years)?, 10_000)?;
+    let call = call_value(spot, strike, sigma_sqrt_t)?;
+    let value = if series.call {
+        call
+    } else {
+        // put-call parity at a zero rate
+        (call - spot + strike).max(0)
+    };
+    match u64::try_from(value) {
+        Err(_) => Err(overflow()),
+        Ok(num) => Ok(num),
+    }
+}
+
+// quote for `amount` option units at `fair` per contract, skewed by the pool's inventory.
+// each unit is marked up by `skew_bps` times the fraction of `target` the pool is short of
+// (and down when it holds more), so integrating over the trade prices it at the midpoint inventory
+pub fn skewed_quote(fair: u64, skew_bps: u64, target: u64, reserve: u64, amount: u64, buy: bool, price_scale: u64) -> Result<u64> {
+    let rounding = if buy { Rounding::Up } else { Rounding::Down };
+    let base = mul_div(amount, fair, price_scale, rounding)?;
+    if target == 0 {
+        return Ok(base)
+    }
+    // twice the inventory at the middle of the trade
+    let midpoint = if buy { 2 * reserve as i128 - amount as i128 } else { 2 * reserve as i128 + amount as i128 };
+    let scaled = 2 * target as i128 * 10_000 + skew_bps as i128 * (2 * target as i128 - midpoint);
+    let multiplier_bps = match rounding {
+        Rounding::Up => scaled.div_euclid(2 * target as i128) + (scaled.rem_euclid(2 * target as i128) != 0) as i128,
+        Rounding::Down => scaled.div_euclid(2 * target as i128),
+    };
+    let multiplier_bps = match u64::try_from(multiplier_bps.max(0)) {
+        Err(_) => return Err(overflow()),
+        Ok(num) => num,
+    };
+    mul_div(base, multiplier_bps, 10_000, rounding)
+}
+
+// marginal quote per contract at the pool's current inventory, the price of the next unit traded
+pub fn skewed_price(fair: u64, skew_bps: u64, target: u64, reserve: u64) -> Result<u64> {
+    if target == 0 {
+        return Ok(fair)
+    }
+    let multiplier_bps = (target as i128 * 10_000 + skew_bps as i128 * (target as i128 - reserve as i128)).div_euclid(target as i128);
+    match u64::try_from(multiplier_bps.max(0)) {
+        Err(_) => Err(overflow()),
+        Ok(num) => mul_div(fair, num, 10_000, Rounding::Down),
+    }
+}
+
+// dutch auction price `elapsed` seconds into `duration`, falling by a constant fraction each second
+// from `start` to `end`: start * (end / start)^(elapsed / duration), rounded up
+pub fn exponential_decay(start: u64, end: u64, elapsed: u64, duration: u64) -> Result<u64> {
+    if end >= start || duration == 0 {
+        return Ok(start)
+    }
+    let exponent = ln(end as i128 * WAD / start as i128)? * elapsed as i128 / duration as i128;
+    let value = (start as u128 * exp(exponent)? as u128).div_ceil(WAD as u128);
+    match u64::try_from(value) {
+        Err(_) => Err(overflow()),
+        Ok(num) => Ok(num.clamp(end, start)),
+    }
+}
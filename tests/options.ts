@@ -1,9 +1,11 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Options } from "../target/types/options";
-import { Keypair, PublicKey, LAMPORTS_PER_SOL } from "@solana/web3.js";
-import {createMint, getAssociatedTokenAddressSync, getOrCreateAssociatedTokenAccount, mintTo, getAccount} from "@solana/spl-token";
+import { Keypair, PublicKey, LAMPORTS_PER_SOL, SystemProgram, Transaction } from "@solana/web3.js";
+import {createMint, getAssociatedTokenAddressSync, TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, getOrCreateAssociatedTokenAccount, mintTo, getAccount, createWrappedNativeAccount, NATIVE_MINT, transfer as transferTokens} from "@solana/spl-token";
+import * as chai from "chai";
 import { assert } from "chai";
+import { createHash } from "crypto";
 
 describe("options", () => {
   // Configure the client to use the local cluster.
@@ -17,6 +19,10 @@ describe("options", () => {
   )
   const OPTION_DECIMALS: number = 6;
   const MINT_AMOUNT: number = 100000 * 10 ** OPTION_DECIMALS;
+  // strike in quote base units per contract, and underlying base units per contract
+  const STRIKE: number = 200 * 10 ** OPTION_DECIMALS;
+  const CONTRACT_MULTIPLIER: number = 10 ** OPTION_DECIMALS;
+  const U64_MAX = new anchor.BN("18446744073709551615");
   const mintToken = async () => {
     const mint = await createMint(
       provider.connection,
@@ -41,88 +47,273 @@ describe("options", () => {
     );
     return { tokenAccount: tokenAccount.address, mint }
   };
+  // a second writer holding underlying and quote of the given mints, both minted by the wallet
+  const fundedWriter = async (underlyingMint: PublicKey, quoteMint: PublicKey) => {
+    const keypair = Keypair.generate();
+    await provider.connection.requestAirdrop(keypair.publicKey, LAMPORTS_PER_SOL);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+    const holdings = [];
+    for (const mint of [underlyingMint, quoteMint]) {
+      const tokenAccount = await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        wallet.payer,
+        mint,
+        keypair.publicKey,
+      );
+      await mintTo(provider.connection, wallet.payer, mint, tokenAccount.address, wallet.payer, MINT_AMOUNT);
+      holdings.push({ mint, tokenAccount: tokenAccount.address });
+    }
+    return { keypair, underlying: holdings[0], quote: holdings[1] };
+  };
+  const positionMetas = (positions: PublicKey[]) => positions.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false }));
+  const [treasury] = PublicKey.findProgramAddressSync(
+    [Buffer.from("treasury")],
+    program.programId,
+  );
+  // the loader account holding the upgrade authority allowed to initialize
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111"),
+  );
+  // protocol fees are collected per quote mint
+  const findTreasuryVault = (quoteMint: PublicKey) => PublicKey.findProgramAddressSync(
+    [Buffer.from("treasury_vault"), quoteMint.toBuffer()],
+    program.programId,
+  )[0];
   it("initialized", async () => {
     // Add your test here.
     await program.methods.initialize().accounts({
       signer: wallet.publicKey,
-      programAuthority
+      programAuthority,
+      treasury,
+      program: program.programId,
+      programData,
     }).rpc();
+    const treasuryData = await program.account.treasury.fetch(treasury);
+    assert(treasuryData.admin.equals(wallet.publicKey), "admin not set");
   });
-  const createOption = async (call: boolean, resellable: boolean, dateChange: number = 1000000) => {
-    const { mint: underlyingMint, tokenAccount: userUnderlyingTokenAccount } = await mintToken();
-    const [underlyingTokenAccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("underlying_token"), underlyingMint.toBuffer()],
-      program.programId
+  type TokenHolding = { mint: PublicKey, tokenAccount: PublicKey };
+  type SeriesTerms = {
+    call: boolean,
+    resellable: boolean,
+    date: number,
+    underlying: TokenHolding,
+    quote: TokenHolding,
+    strike?: number,
+    multiplier?: number,
+    amount?: number,
+    writer?: Keypair,
+    // cash settled series read this oracle, physically settled ones use the default pubkey
+    oracle?: PublicKey,
+    exerciseStyle?: object,
+  };
+  const AMERICAN = { american: {} };
+  const findOptionMint = (underlyingMint: PublicKey, quoteMint: PublicKey, strike: number, multiplier: number, date: number, call: boolean, resellable: boolean, oracle: PublicKey, exerciseStyle: object) => {
+    const [optionMint] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("option_mint"),
+        underlyingMint.toBuffer(),
+        quoteMint.toBuffer(),
+        new anchor.BN(strike).toArrayLike(Buffer, "be", 8),
+        new anchor.BN(multiplier).toArrayLike(Buffer, "be", 8),
+        new anchor.BN(date).toArrayLike(Buffer, "be", 8),
+        Buffer.from([call ? 1 : 0]),
+        Buffer.from([resellable ? 1 : 0]),
+        Buffer.from([oracle.equals(PublicKey.default) ? 0 : 1]),
+        oracle.toBuffer(),
+        createHash("sha256").update(program.coder.types.encode("ExerciseStyle", exerciseStyle)).digest(),
+      ],
+      program.programId,
     );
-    const optionMint = Keypair.generate();
-    const userOptionTokenAccount = getAssociatedTokenAddressSync(optionMint.publicKey, wallet.publicKey);
+    return optionMint;
+  }
+  const writeOption = async ({ call, resellable, date, underlying, quote, strike = STRIKE, multiplier = CONTRACT_MULTIPLIER, amount = 400 * 10 ** OPTION_DECIMALS, writer, oracle = PublicKey.default, exerciseStyle = AMERICAN }: SeriesTerms) => {
+    const signer = writer ? writer.publicKey : wallet.publicKey;
+    const { mint: underlyingMint, tokenAccount: userUnderlyingTokenAccount } = underlying;
+    const { mint: quoteMint, tokenAccount: userQuoteTokenAccount } = quote;
+    const optionMint = findOptionMint(underlyingMint, quoteMint, strike, multiplier, date, call, resellable, oracle, exerciseStyle);
+    // series that can't be resold are minted as non-transferable token-2022 options
+    const optionTokenProgram = resellable ? TOKEN_PROGRAM_ID : TOKEN_2022_PROGRAM_ID;
+    const userOptionTokenAccount = getAssociatedTokenAddressSync(optionMint, signer, false, optionTokenProgram);
     const [optionDataAccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("option_data_account"), optionMint.publicKey.toBuffer()],
+      [Buffer.from("option_data_account"), optionMint.toBuffer()],
       program.programId,
     );
-    const date = Math.floor(Date.now() / 1000 + dateChange);
+    const [writerPosition] = PublicKey.findProgramAddressSync(
+      [Buffer.from("writer_position"), optionDataAccount.toBuffer(), signer.toBuffer()],
+      program.programId,
+    );
+    // each series holds its collateral in its own vaults
+    const [underlyingTokenAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("underlying_vault"), optionDataAccount.toBuffer()],
+      program.programId
+    );
+    const [quoteTokenAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("quote_vault"), optionDataAccount.toBuffer()],
+      program.programId
+    );
     const accounts = {
-      signer: wallet.publicKey,
+      signer,
       underlyingMint,
       userUnderlyingTokenAccount,
       underlyingTokenAccount,
-      optionMint: optionMint.publicKey,
+      quoteMint,
+      userQuoteTokenAccount,
+      quoteTokenAccount,
+      oracle,
+      optionMint,
       userOptionTokenAccount,
       optionDataAccount,
+      writerPosition,
       programAuthority,
+      optionTokenProgram,
     }
     // for (const account in accounts) {
     //   console.log(`${account}: ${accounts[account].toString()}`);
     // }
-    await program.methods.create(new anchor.BN(date), new anchor.BN(200), new anchor.BN(400 * 10 ** OPTION_DECIMALS), call, resellable).accounts(accounts).signers([optionMint]).rpc();
+    await program.methods.create(new anchor.BN(date), new anchor.BN(strike), new anchor.BN(multiplier), new anchor.BN(amount), call, resellable, !oracle.equals(PublicKey.default), exerciseStyle as any)
+      .accounts(accounts)
+      .signers(writer ? [writer] : [])
+      .rpc();
     return {...accounts, date};
   }
+  const createOption = async (call: boolean, resellable: boolean, dateChange: number = 1000000, quote?: TokenHolding) => {
+    const underlying = await mintToken();
+    const date = Math.floor(Date.now() / 1000 + dateChange);
+    return await writeOption({ call, resellable, date, underlying, quote: quote ?? await mintToken() });
+  }
   it("creates option mint", async () => {
-    const { optionDataAccount, date, underlyingMint } = await createOption(false, false);
+    const { optionDataAccount, date, underlyingMint, quoteMint, quoteTokenAccount } = await createOption(false, false);
     const optionData = await program.account.optionDataAccount.fetch(optionDataAccount);
     assert(optionData.endTime.toNumber() === date);
     assert(optionData.amountUnexercised.toNumber() === 400 * 10 ** OPTION_DECIMALS);
-    assert(optionData.strikePrice.toNumber() === 200);
+    assert(optionData.strikePrice.toNumber() === STRIKE);
     assert(optionData.underlyingMint.equals(underlyingMint));
     assert(optionData.creator.equals(wallet.publicKey));
     assert(optionData.call === false);
+    assert(optionData.quoteMint.equals(quoteMint));
+    assert(optionData.contractMultiplier.toNumber() === CONTRACT_MULTIPLIER);
+    assert(optionData.priceScale.toNumber() === 10 ** OPTION_DECIMALS);
+    const quoteVault = await getAccount(provider.connection, quoteTokenAccount);
+    assert(quoteVault.amount === BigInt(200 * 400 * 10 ** OPTION_DECIMALS), "put collateral not deposited");
 
     {
       const { optionDataAccount, date, underlyingMint } = await createOption(true, false); 
       const optionData = await program.account.optionDataAccount.fetch(optionDataAccount);
       assert(optionData.endTime.toNumber() === date);
       assert(optionData.amountUnexercised.toNumber() === 400 * 10 ** OPTION_DECIMALS);
-      assert(optionData.strikePrice.toNumber() === 200);
+      assert(optionData.strikePrice.toNumber() === STRIKE);
       assert(optionData.underlyingMint.equals(underlyingMint));
       assert(optionData.creator.equals(wallet.publicKey));
       assert(optionData.call === true);
     }
   });
+  it("writes a series whose mint address was funded in advance", async () => {
+    const underlying = await mintToken();
+    const quote = await mintToken();
+    const date = Math.floor(Date.now() / 1000 + 1000000);
+    // a single lamport sent to the mint pda must not block the series
+    const optionMint = findOptionMint(underlying.mint, quote.mint, STRIKE, CONTRACT_MULTIPLIER, date, true, false, PublicKey.default, AMERICAN);
+    await provider.sendAndConfirm(new Transaction().add(SystemProgram.transfer({
+      fromPubkey: wallet.publicKey,
+      toPubkey: optionMint,
+      lamports: 1,
+    })));
+    const series = await writeOption({ call: true, resellable: false, date, underlying, quote });
+    assert(series.optionMint.equals(optionMint));
+    const optionBalance = (await getAccount(provider.connection, series.userOptionTokenAccount, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+    assert(optionBalance === BigInt(400 * 10 ** OPTION_DECIMALS), "options not minted");
+  });
+  it("mints repeated writes into the same series", async () => {
+    const underlying = await mintToken();
+    const quote = await mintToken();
+    const date = Math.floor(Date.now() / 1000 + 1000000);
+    const first = await writeOption({ call: true, resellable: true, date, underlying, quote });
+    const second = await writeOption({ call: true, resellable: true, date, underlying, quote });
+    assert(first.optionMint.equals(second.optionMint), "series mint not reused");
+    const optionData = await program.account.optionDataAccount.fetch(first.optionDataAccount);
+    assert(optionData.amountUnexercised.toNumber() === 2 * 400 * 10 ** OPTION_DECIMALS);
+    const optionBalance = (await getAccount(provider.connection, first.userOptionTokenAccount)).amount;
+    assert(optionBalance === BigInt(2 * 400 * 10 ** OPTION_DECIMALS));
+    const other = await writeOption({ call: true, resellable: true, date, underlying, quote, strike: 300 * 10 ** OPTION_DECIMALS, amount: 10 });
+    assert(!other.optionMint.equals(first.optionMint), "different terms share a mint");
+    assert(!other.underlyingTokenAccount.equals(first.underlyingTokenAccount), "series share a collateral vault");
+  });
+  it("scales collateral by the contract multiplier", async () => {
+    const underlying = await mintToken();
+    const quote = await mintToken();
+    const date = Math.floor(Date.now() / 1000 + 1000000);
+    const before = (await getAccount(provider.connection, underlying.tokenAccount)).amount;
+    // half an underlying token per contract, three contracts
+    const { writerPosition } = await writeOption({ call: true, resellable: false, date, underlying, quote, multiplier: 5 * 10 ** 5, amount: 3 * 10 ** OPTION_DECIMALS });
+    const after = (await getAccount(provider.connection, underlying.tokenAccount)).amount;
+    assert(before - after === BigInt(15 * 10 ** 5), "wrong call collateral");
+    const position = await program.account.writerPosition.fetch(writerPosition);
+    assert(position.collateralDeposited.toNumber() === 15 * 10 ** 5);
+    // a single option base unit still rounds its collateral up
+    const { writerPosition: dustPosition } = await writeOption({ call: false, resellable: false, date, underlying, quote, strike: 3, amount: 1 });
+    const dust = await program.account.writerPosition.fetch(dustPosition);
+    assert(dust.collateralDeposited.toNumber() === 1, "collateral rounded against the protocol");
+  });
+  it("keeps options that can't be resold out of transfers and markets", async () => {
+    const { optionDataAccount, optionMint, userOptionTokenAccount, quoteMint } = await createOption(false, false);
+    const mintInfo = await provider.connection.getAccountInfo(optionMint);
+    assert(mintInfo.owner.equals(TOKEN_2022_PROGRAM_ID), "option mint not under token-2022");
+    const other = Keypair.generate();
+    const otherOptions = await getOrCreateAssociatedTokenAccount(provider.connection, wallet.payer, optionMint, other.publicKey, false, undefined, undefined, TOKEN_2022_PROGRAM_ID);
+    try {
+      await transferTokens(provider.connection, wallet.payer, userOptionTokenAccount, otherOptions.address, wallet.publicKey, 1, [], undefined, TOKEN_2022_PROGRAM_ID);
+      assert(false, "transferred a non-transferable option");
+    } catch (e) {
+      assert(!(e instanceof chai.AssertionError), "transferred a non-transferable option");
+    }
+    const price = new anchor.BN(1);
+    const [listAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("listing"), optionMint.toBuffer(), wallet.publicKey.toBuffer(), price.toArrayLike(Buffer, "be", 8)],
+      program.programId,
+    );
+    const [listingVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("listing_vault"), listAccount.toBuffer()],
+      program.programId,
+    );
+    try {
+      await program.methods.list(new anchor.BN(1), price, null).accounts({
+        signer: wallet.publicKey,
+        optionMint,
+        userOptionTokenAccount,
+        optionDataAccount,
+        listingVault,
+        quoteMint,
+        treasuryVault: findTreasuryVault(quoteMint),
+        listAccount,
+        programAuthority,
+      }).rpc();
+      assert(false, "listed a non-resellable option");
+    } catch (e) {
+      assert(!(e instanceof chai.AssertionError), "listed a non-resellable option");
+    }
+  });
   it("lists multiple of same", async () => {
     const {
       optionDataAccount, underlyingMint, userUnderlyingTokenAccount, 
-      underlyingTokenAccount, optionMint, userOptionTokenAccount, } = await createOption(false, false);
-    const [programHolderAccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("holder_account"), optionMint.toBuffer()],
-      program.programId,
-    );
+      underlyingTokenAccount, optionMint, userOptionTokenAccount, quoteMint } = await createOption(false, true);
     const price = new anchor.BN(1);
     const [listAccount] = PublicKey.findProgramAddressSync(
       [Buffer.from("listing"), optionMint.toBuffer(), wallet.publicKey.toBuffer(), price.toArrayLike(Buffer, "be", 8)],
       program.programId,
     );
-    await program.methods.createHolderAccount().accounts({
-      signer: wallet.publicKey,
-      optionMint,
-      programAuthority,
-      programHolderAccount,
-    }).rpc();
-    await program.methods.list(new anchor.BN(400), price).accounts({
+    const [listingVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("listing_vault"), listAccount.toBuffer()],
+      program.programId,
+    );
+    await program.methods.list(new anchor.BN(400), price, null).accounts({
       signer: wallet.publicKey,
       optionMint,
       userOptionTokenAccount,
       optionDataAccount,
-      programHolderAccount,
+      listingVault,
+      quoteMint,
+      treasuryVault: findTreasuryVault(quoteMint),
       listAccount,
       programAuthority,
     }).rpc();
@@ -134,12 +325,14 @@ describe("options", () => {
     assert(listAccountData.underlyingMint.equals(underlyingMint), "wrong underlying");
     assert(listAccountData.price.toNumber() === price.toNumber(), "wrong price");
     for (let i = 0; i < 3; i++) {
-      await program.methods.list(new anchor.BN(400), price).accounts({
+      await program.methods.list(new anchor.BN(400), price, null).accounts({
         signer: wallet.publicKey,
         optionMint,
         userOptionTokenAccount,
         optionDataAccount,
-        programHolderAccount,
+        listingVault,
+        quoteMint,
+        treasuryVault: findTreasuryVault(quoteMint),
         listAccount,
         programAuthority,
       }).rpc();
@@ -149,28 +342,25 @@ describe("options", () => {
   });
   it("Buys successfully", async () => {
     const { optionDataAccount, underlyingMint, userUnderlyingTokenAccount, 
-      underlyingTokenAccount, optionMint, userOptionTokenAccount, } = await createOption(false, false);
-    const [programHolderAccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("holder_account"), optionMint.toBuffer()],
-      program.programId,
-    );
-    const price = new anchor.BN(1);
+      underlyingTokenAccount, optionMint, userOptionTokenAccount, quoteMint, userQuoteTokenAccount } = await createOption(false, true);
+    // 10000 quote base units per option base unit
+    const price = new anchor.BN(10 ** 10);
     const [listAccount] = PublicKey.findProgramAddressSync(
       [Buffer.from("listing"), optionMint.toBuffer(), wallet.publicKey.toBuffer(), price.toArrayLike(Buffer, "be", 8)],
       program.programId,
     );
-    await program.methods.createHolderAccount().accounts({
-      signer: wallet.publicKey,
-      optionMint,
-      programAuthority,
-      programHolderAccount,
-    }).rpc();
-    await program.methods.list(new anchor.BN(400), price).accounts({
+    const [listingVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("listing_vault"), listAccount.toBuffer()],
+      program.programId,
+    );
+    await program.methods.list(new anchor.BN(400), price, null).accounts({
       signer: wallet.publicKey,
       optionMint,
       userOptionTokenAccount,
       optionDataAccount,
-      programHolderAccount,
+      listingVault,
+      quoteMint,
+      treasuryVault: findTreasuryVault(quoteMint),
       listAccount,
       programAuthority,
     }).rpc();
@@ -183,89 +373,1168 @@ describe("options", () => {
       optionMint,
       account.publicKey
     )
+    const accountQuoteHolder = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      wallet.payer,
+      quoteMint,
+      account.publicKey
+    )
+    await mintTo(provider.connection, wallet.payer, quoteMint, accountQuoteHolder.address, wallet.payer, 10 ** 6);
     await provider.connection.requestAirdrop(account.publicKey, LAMPORTS_PER_SOL);
     await new Promise((resolve) => setTimeout(resolve, 1000));
+    const sellerQuoteBefore = (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
     for (let i = 0; i < 3; i++) {
-      await program.methods.buy(p, new anchor.BN(1)).accounts({
+      await program.methods.buy(p, new anchor.BN(1), new anchor.BN(10000), null).accounts({
         signer: account.publicKey,
         optionMint,
+        optionDataAccount,
         owner: wallet.publicKey,
         listing: listAccount,
-        programHolderAccount,
+        listingVault,
         userHolderAccount: accountHolder.address,
+        userQuoteTokenAccount: accountQuoteHolder.address,
+        ownerQuoteTokenAccount: userQuoteTokenAccount,
+        treasuryVault: findTreasuryVault(quoteMint),
         programAuthority,
       }).signers([account]).rpc();
       const accountHolderData = await getAccount(provider.connection, accountHolder.address);
       assert(accountHolderData.amount === BigInt(i + 1));
     }
+    const sellerQuoteAfter = (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    // 2 basis points of each 10000 quote fill go to the treasury
+    assert(sellerQuoteAfter === sellerQuoteBefore + BigInt(3 * 9998), "seller not paid in quote");
+    const treasuryVault = findTreasuryVault(quoteMint);
+    assert((await getAccount(provider.connection, treasuryVault)).amount === BigInt(3 * 2), "listing fee not collected");
+    const adminQuoteBefore = (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    await program.methods.withdrawFees(new anchor.BN(6)).accounts({
+      signer: wallet.publicKey,
+      treasury,
+      quoteMint,
+      treasuryVault,
+      adminQuoteTokenAccount: userQuoteTokenAccount,
+      programAuthority,
+    }).rpc();
+    assert((await getAccount(provider.connection, userQuoteTokenAccount)).amount === adminQuoteBefore + BigInt(6), "fees not withdrawn");
+    try {
+      await program.methods.withdrawFees(new anchor.BN(0)).accounts({
+        signer: account.publicKey,
+        treasury,
+        quoteMint,
+        treasuryVault,
+        adminQuoteTokenAccount: accountQuoteHolder.address,
+        programAuthority,
+      }).signers([account]).rpc();
+      assert(false, "non admin withdrew fees");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "WrongOwner");
+    }
+  });
+  it("fills listings partially and closes them once filled", async () => {
+    const { optionDataAccount, optionMint, userOptionTokenAccount, quoteMint, userQuoteTokenAccount } = await createOption(false, true);
+    const price = new anchor.BN(10 ** 10);
+    const [listAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("listing"), optionMint.toBuffer(), wallet.publicKey.toBuffer(), price.toArrayLike(Buffer, "be", 8)],
+      program.programId,
+    );
+    const [listingVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("listing_vault"), listAccount.toBuffer()],
+      program.programId,
+    );
+    await program.methods.list(new anchor.BN(5), price, null).accounts({
+      signer: wallet.publicKey,
+      optionMint,
+      userOptionTokenAccount,
+      optionDataAccount,
+      listingVault,
+      quoteMint,
+      treasuryVault: findTreasuryVault(quoteMint),
+      listAccount,
+      programAuthority,
+    }).rpc();
+    const buyer = Keypair.generate();
+    const buyerOptions = await getOrCreateAssociatedTokenAccount(provider.connection, wallet.payer, optionMint, buyer.publicKey);
+    const buyerQuote = await getOrCreateAssociatedTokenAccount(provider.connection, wallet.payer, quoteMint, buyer.publicKey);
+    await mintTo(provider.connection, wallet.payer, quoteMint, buyerQuote.address, wallet.payer, 10 ** 6);
+    await provider.connection.requestAirdrop(buyer.publicKey, LAMPORTS_PER_SOL);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+    const fill = (amount: number) => program.methods.buy(price, new anchor.BN(amount), U64_MAX, null).accounts({
+      signer: buyer.publicKey,
+      optionMint,
+      optionDataAccount,
+      owner: wallet.publicKey,
+      listing: listAccount,
+      listingVault,
+      userHolderAccount: buyerOptions.address,
+      userQuoteTokenAccount: buyerQuote.address,
+      ownerQuoteTokenAccount: userQuoteTokenAccount,
+      treasuryVault: findTreasuryVault(quoteMint),
+      programAuthority,
+    }).signers([buyer]).rpc({ commitment: "confirmed" });
+    const signature = await fill(2);
+    assert((await program.account.listing.fetch(listAccount)).amount.toNumber() === 3, "listing not decremented");
+    // every fill is recorded in the transaction logs
+    const tx = await provider.connection.getTransaction(signature, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+    const events = [...new anchor.EventParser(program.programId, program.coder).parseLogs(tx.meta.logMessages)];
+    assert(events.length === 1 && events[0].name === "ListingFilled", "fill not recorded");
+    assert(events[0].data.amount.toNumber() === 2 && events[0].data.remaining.toNumber() === 3, "wrong fill record");
+    try {
+      await fill(4);
+      assert(false, "filled past the listing");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "ListingEmpty");
+    }
+    const sellerLamports = await provider.connection.getBalance(wallet.publicKey);
+    await fill(3);
+    assert((await getAccount(provider.connection, buyerOptions.address)).amount === BigInt(5), "options not delivered");
+    assert(await program.account.listing.fetchNullable(listAccount) === null, "filled listing not closed");
+    assert(await provider.connection.getAccountInfo(listingVault) === null, "filled listing vault not closed");
+    assert(await provider.connection.getBalance(wallet.publicKey) > sellerLamports, "rent not returned to the seller");
+  });
+  it("escrows bids and fills them from sellers", async () => {
+    const { optionDataAccount, optionMint, userOptionTokenAccount, quoteMint, userQuoteTokenAccount } = await createOption(false, true);
+    const bidder = Keypair.generate();
+    const bidderOptions = await getOrCreateAssociatedTokenAccount(provider.connection, wallet.payer, optionMint, bidder.publicKey);
+    const bidderQuote = await getOrCreateAssociatedTokenAccount(provider.connection, wallet.payer, quoteMint, bidder.publicKey);
+    await mintTo(provider.connection, wallet.payer, quoteMint, bidderQuote.address, wallet.payer, 10 ** 6);
+    await provider.connection.requestAirdrop(bidder.publicKey, LAMPORTS_PER_SOL);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+    const bidderQuoteBalance = async () => (await getAccount(provider.connection, bidderQuote.address)).amount;
+    const findBid = (price: anchor.BN) => {
+      const [bid] = PublicKey.findProgramAddressSync(
+        [Buffer.from("bid"), optionMint.toBuffer(), bidder.publicKey.toBuffer(), price.toArrayLike(Buffer, "be", 8)],
+        program.programId,
+      );
+      const [bidVault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("bid_vault"), bid.toBuffer()],
+        program.programId,
+      );
+      return { bid, bidVault };
+    };
+    const placeBid = (amount: number, price: anchor.BN) => program.methods.placeBid(new anchor.BN(amount), price).accounts({
+      signer: bidder.publicKey,
+      optionMint,
+      optionDataAccount,
+      quoteMint,
+      userQuoteTokenAccount: bidderQuote.address,
+      treasuryVault: findTreasuryVault(quoteMint),
+      ...findBid(price),
+      programAuthority,
+    }).signers([bidder]).rpc();
+    // 10000 quote base units per option base unit
+    const price = new anchor.BN(10 ** 10);
+    const { bid, bidVault } = findBid(price);
+    await placeBid(5, price);
+    assert((await getAccount(provider.connection, bidVault)).amount === BigInt(50000), "quote not escrowed");
+    const sell = (amount: number, minQuoteOut = 0) => program.methods.sellToBid(price, new anchor.BN(amount), new anchor.BN(minQuoteOut), null).accounts({
+      signer: wallet.publicKey,
+      optionMint,
+      optionDataAccount,
+      bidder: bidder.publicKey,
+      bid,
+      bidVault,
+      userOptionTokenAccount,
+      userQuoteTokenAccount,
+      bidderOptionTokenAccount: bidderOptions.address,
+      bidderQuoteTokenAccount: bidderQuote.address,
+      treasuryVault: findTreasuryVault(quoteMint),
+      programAuthority,
+    }).rpc();
+    const expectError = async (attempt: Promise<string>, code: string) => {
+      try {
+        await attempt;
+        assert(false, `expected ${code}`);
+      } catch (e) {
+        assert(e instanceof anchor.AnchorError, "unexpected error");
+        assert(e.error.errorCode.code === code);
+      }
+    };
+    const sellerQuoteBefore = (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    await expectError(sell(2, 19997), "SlippageExceeded");
+    await sell(2, 19996);
+    // 2 basis points of the fill go to the treasury
+    assert((await getAccount(provider.connection, userQuoteTokenAccount)).amount === sellerQuoteBefore + BigInt(19996), "seller not paid from escrow");
+    assert((await getAccount(provider.connection, bidderOptions.address)).amount === BigInt(2), "options not delivered to the bidder");
+    assert((await program.account.bid.fetch(bid)).amount.toNumber() === 3, "bid not decremented");
+    await expectError(sell(4), "BidEmpty");
+    await sell(3);
+    assert(await program.account.bid.fetchNullable(bid) === null, "filled bid not closed");
+    assert(await provider.connection.getAccountInfo(bidVault) === null, "filled bid vault not closed");
+    // a cancelled bid returns its whole escrow
+    const otherPrice = new anchor.BN(2 * 10 ** 10);
+    const before = await bidderQuoteBalance();
+    await placeBid(10, otherPrice);
+    assert(await bidderQuoteBalance() === before - BigInt(200000), "quote not escrowed");
+    await program.methods.cancelBid(otherPrice).accounts({
+      signer: bidder.publicKey,
+      optionMint,
+      ...findBid(otherPrice),
+      userQuoteTokenAccount: bidderQuote.address,
+      programAuthority,
+    }).signers([bidder]).rpc();
+    assert(await bidderQuoteBalance() === before, "escrow not returned");
+    assert(await program.account.bid.fetchNullable(findBid(otherPrice).bid) === null, "cancelled bid not closed");
+  });
+  it("matches orders on the book in price-time priority", async () => {
+    const { optionDataAccount, optionMint, userOptionTokenAccount, quoteMint, userQuoteTokenAccount } = await createOption(false, true);
+    const [orderBook] = PublicKey.findProgramAddressSync([Buffer.from("order_book"), optionMint.toBuffer()], program.programId);
+    const [bookOptionVault] = PublicKey.findProgramAddressSync([Buffer.from("book_option_vault"), orderBook.toBuffer()], program.programId);
+    const [bookQuoteVault] = PublicKey.findProgramAddressSync([Buffer.from("book_quote_vault"), orderBook.toBuffer()], program.programId);
+    const treasuryVault = findTreasuryVault(quoteMint);
+    await program.methods.createOrderBook().accounts({
+      signer: wallet.publicKey,
+      optionMint,
+      optionDataAccount,
+      quoteMint,
+      orderBook,
+      bookOptionVault,
+      bookQuoteVault,
+      treasuryVault,
+      programAuthority,
+    }).rpc();
+    const buyer = Keypair.generate();
+    const buyerOptions = await getOrCreateAssociatedTokenAccount(provider.connection, wallet.payer, optionMint, buyer.publicKey);
+    const buyerQuote = await getOrCreateAssociatedTokenAccount(provider.connection, wallet.payer, quoteMint, buyer.publicKey);
+    await mintTo(provider.connection, wallet.payer, quoteMint, buyerQuote.address, wallet.payer, 10 ** 6);
+    await provider.connection.requestAirdrop(buyer.publicKey, LAMPORTS_PER_SOL);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+    const bookAccounts = { optionMint, optionDataAccount, orderBook, bookOptionVault, bookQuoteVault, treasuryVault, programAuthority };
+    const sellerAccounts = { ...bookAccounts, signer: wallet.publicKey, userOptionTokenAccount, userQuoteTokenAccount };
+    const buyerAccounts = { ...bookAccounts, signer: buyer.publicKey, userOptionTokenAccount: buyerOptions.address, userQuoteTokenAccount: buyerQuote.address };
+    const ask = (price: number, amount: number) => program.methods.placeOrder({ ask: {} }, new anchor.BN(price), new anchor.BN(amount), false).accounts(sellerAccounts).rpc();
+    const quoteBalance = async (account: PublicKey) => (await getAccount(provider.connection, account)).amount;
+    // orders that may rest need at least a hundredth of a contract
+    try {
+      await ask(10 ** 6, 10 ** 4 - 1);
+      assert(false, "rested an order below the minimum");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "InvalidOrder");
+    }
+    // one quote base unit per option base unit, the second ask at the same price queues behind the first
+    await ask(2 * 10 ** 6, 3 * 10 ** 4);
+    await ask(10 ** 6, 3 * 10 ** 4);
+    await ask(10 ** 6, 2 * 10 ** 4);
+    const buyerBefore = await quoteBalance(buyerQuote.address);
+    await program.methods.placeOrder({ bid: {} }, new anchor.BN(2 * 10 ** 6), new anchor.BN(6 * 10 ** 4), false).accounts(buyerAccounts).signers([buyer]).rpc();
+    assert((await getAccount(provider.connection, buyerOptions.address)).amount === BigInt(6 * 10 ** 4), "bid not filled");
+    // both cheaper asks fill before one unit of the dearer one
+    assert(buyerBefore - await quoteBalance(buyerQuote.address) === BigInt(30000 + 20000 + 20000), "fills not at the resting prices");
+    let book = await program.account.orderBook.fetch(orderBook);
+    assert(book.bidCount.toNumber() === 0 && book.askCount.toNumber() === 1, "filled orders left on the book");
+    assert(book.asks[0].amount.toNumber() === 2 * 10 ** 4 && book.asks[0].price.toNumber() === 2 * 10 ** 6, "wrong order left resting");
+    assert(book.eventCount.toNumber() === 3, "fills not queued");
+    // makers are paid when their fills are consumed, less 2 basis points
+    const sellerBefore = await quoteBalance(userQuoteTokenAccount);
+    const consume = (destinations: PublicKey[]) => program.methods.consumeEvents().accounts(bookAccounts)
+      .remainingAccounts(destinations.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false }))).rpc();
+    await consume([userQuoteTokenAccount, userQuoteTokenAccount, userQuoteTokenAccount]);
+    assert(await quoteBalance(userQuoteTokenAccount) === sellerBefore + BigInt(29994 + 19996 + 19996), "makers not paid");
+    assert((await program.account.orderBook.fetch(orderBook)).eventCount.toNumber() === 0, "events not consumed");
+    // a resting bid is filled by a later ask, the taking seller is paid immediately
+    await program.methods.placeOrder({ bid: {} }, new anchor.BN(10 ** 6), new anchor.BN(2 * 10 ** 4), false).accounts(buyerAccounts).signers([buyer]).rpc();
+    const sellerMid = await quoteBalance(userQuoteTokenAccount);
+    await program.methods.placeOrder({ ask: {} }, new anchor.BN(10 ** 6), new anchor.BN(5 * 10 ** 4), true).accounts(sellerAccounts).rpc();
+    assert(await quoteBalance(userQuoteTokenAccount) === sellerMid + BigInt(19996), "taker not paid");
+    book = await program.account.orderBook.fetch(orderBook);
+    assert(book.askCount.toNumber() === 1, "immediate or cancel remainder rested");
+    await consume([buyerOptions.address]);
+    assert((await getAccount(provider.connection, buyerOptions.address)).amount === BigInt(8 * 10 ** 4), "bid maker not delivered");
+    // cancelling returns the escrowed options
+    const optionsBefore = (await getAccount(provider.connection, userOptionTokenAccount)).amount;
+    await program.methods.cancelOrder({ ask: {} }, book.asks[0].sequence).accounts(sellerAccounts).rpc();
+    assert((await getAccount(provider.connection, userOptionTokenAccount)).amount === optionsBefore + BigInt(2 * 10 ** 4), "escrow not returned");
+    try {
+      await program.methods.cancelOrder({ ask: {} }, book.asks[0].sequence).accounts(sellerAccounts).rpc();
+      assert(false, "cancelled twice");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "OrderNotFound");
+    }
+  });
+  it("reprices, reduces, cancels and sweeps listings", async () => {
+    const { optionDataAccount, optionMint, userOptionTokenAccount, quoteMint } = await createOption(false, true);
+    const findListing = (price: anchor.BN) => {
+      const [listing] = PublicKey.findProgramAddressSync(
+        [Buffer.from("listing"), optionMint.toBuffer(), wallet.publicKey.toBuffer(), price.toArrayLike(Buffer, "be", 8)],
+        program.programId,
+      );
+      const [listingVault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("listing_vault"), listing.toBuffer()],
+        program.programId,
+      );
+      return { listing, listingVault };
+    };
+    const list = (amount: number, price: anchor.BN, goodTil: anchor.BN | null = null) => {
+      const { listing, listingVault } = findListing(price);
+      return program.methods.list(new anchor.BN(amount), price, goodTil).accounts({
+        signer: wallet.publicKey,
+        optionMint,
+        userOptionTokenAccount,
+        optionDataAccount,
+        listingVault,
+        quoteMint,
+        treasuryVault: findTreasuryVault(quoteMint),
+        listAccount: listing,
+        programAuthority,
+      }).rpc();
+    };
+    const optionBalance = async () => (await getAccount(provider.connection, userOptionTokenAccount)).amount;
+    const first = new anchor.BN(10 ** 10);
+    const second = new anchor.BN(2 * 10 ** 10);
+    await list(10, first);
+    // repricing moves the whole listing to the new price
+    await program.methods.repriceListing(first, second).accounts({
+      signer: wallet.publicKey,
+      optionMint,
+      ...findListing(first),
+      newListing: findListing(second).listing,
+      newListingVault: findListing(second).listingVault,
+      programAuthority,
+    }).rpc();
+    assert(await program.account.listing.fetchNullable(findListing(first).listing) === null, "old listing not closed");
+    const repriced = await program.account.listing.fetch(findListing(second).listing);
+    assert(repriced.amount.toNumber() === 10 && repriced.price.eq(second), "listing not moved");
+    try {
+      await program.methods.repriceListing(second, second).accounts({
+        signer: wallet.publicKey,
+        optionMint,
+        ...findListing(second),
+        newListing: findListing(second).listing,
+        newListingVault: findListing(second).listingVault,
+        programAuthority,
+      }).rpc();
+      assert(false, "repriced to the same price");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "SamePrice");
+    }
+    const reduce = (amount: number) => program.methods.reduceListing(second, new anchor.BN(amount)).accounts({
+      signer: wallet.publicKey,
+      optionMint,
+      ...findListing(second),
+      ownerTokenAccount: userOptionTokenAccount,
+      programAuthority,
+    }).rpc();
+    let before = await optionBalance();
+    await reduce(4);
+    assert(await optionBalance() === before + BigInt(4), "reduced options not returned");
+    assert((await program.account.listing.fetch(findListing(second).listing)).amount.toNumber() === 6, "listing not reduced");
+    // cancelling doesn't wait for expiry
+    before = await optionBalance();
+    await program.methods.closeListing(second).accounts({
+      signer: wallet.publicKey,
+      optionMint,
+      ...findListing(second),
+      owner: wallet.publicKey,
+      ownerTokenAccount: userOptionTokenAccount,
+      programAuthority,
+    }).rpc();
+    assert(await optionBalance() === before + BigInt(6), "cancelled options not returned");
+    assert(await program.account.listing.fetchNullable(findListing(second).listing) === null, "cancelled listing not closed");
+    // a listing past its good-til time can be swept by anyone
+    await list(5, first, new anchor.BN(Math.floor(Date.now() / 1000) + 2));
+    const sweep = () => program.methods.sweepExpiredListings().accounts({ programAuthority }).remainingAccounts(
+      [findListing(first).listing, findListing(first).listingVault, optionDataAccount, userOptionTokenAccount, wallet.publicKey]
+        .map((pubkey) => ({ pubkey, isWritable: true, isSigner: false }))
+    ).rpc();
+    try {
+      await sweep();
+      assert(false, "swept a live listing");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "ListingNotExpired");
+    }
+    await new Promise((resolve) => setTimeout(resolve, 4000));
+    before = await optionBalance();
+    await sweep();
+    assert(await optionBalance() === before + BigInt(5), "swept options not returned");
+    assert(await program.account.listing.fetchNullable(findListing(first).listing) === null, "swept listing not closed");
+  });
+  it("sells dutch auction listings at their decayed price", async () => {
+    const { optionDataAccount, optionMint, userOptionTokenAccount, quoteMint, userQuoteTokenAccount } = await createOption(false, true);
+    const findListing = (price: anchor.BN) => {
+      const [listing] = PublicKey.findProgramAddressSync(
+        [Buffer.from("listing"), optionMint.toBuffer(), wallet.publicKey.toBuffer(), price.toArrayLike(Buffer, "be", 8)],
+        program.programId,
+      );
+      const [listingVault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("listing_vault"), listing.toBuffer()],
+        program.programId,
+      );
+      return { listing, listingVault };
+    };
+    const now = Math.floor(Date.now() / 1000);
+    const listAuction = (price: anchor.BN, endPrice: number, startTime: number, decay: object) => program.methods.listAuction(new anchor.BN(10), price, {
+      endPrice: new anchor.BN(endPrice),
+      startTime: new anchor.BN(startTime),
+      duration: new anchor.BN(100),
+      decay: decay as any,
+    }, null).accounts({
+      signer: wallet.publicKey,
+      optionMint,
+      userOptionTokenAccount,
+      optionDataAccount,
+      listingVault: findListing(price).listingVault,
+      quoteMint,
+      treasuryVault: findTreasuryVault(quoteMint),
+      listAccount: findListing(price).listing,
+      programAuthority,
+    }).rpc();
+    const buyer = Keypair.generate();
+    const buyerOptions = await getOrCreateAssociatedTokenAccount(provider.connection, wallet.payer, optionMint, buyer.publicKey);
+    const buyerQuote = await getOrCreateAssociatedTokenAccount(provider.connection, wallet.payer, quoteMint, buyer.publicKey);
+    await mintTo(provider.connection, wallet.payer, quoteMint, buyerQuote.address, wallet.payer, 10 ** 6);
+    await provider.connection.requestAirdrop(buyer.publicKey, LAMPORTS_PER_SOL);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+    const buyOne = async (price: anchor.BN) => {
+      const before = (await getAccount(provider.connection, buyerQuote.address)).amount;
+      await program.methods.buy(price, new anchor.BN(1), U64_MAX, null).accounts({
+        signer: buyer.publicKey,
+        optionMint,
+        optionDataAccount,
+        owner: wallet.publicKey,
+        ...findListing(price),
+        userHolderAccount: buyerOptions.address,
+        userQuoteTokenAccount: buyerQuote.address,
+        ownerQuoteTokenAccount: userQuoteTokenAccount,
+        treasuryVault: findTreasuryVault(quoteMint),
+        programAuthority,
+      }).signers([buyer]).rpc();
+      return before - (await getAccount(provider.connection, buyerQuote.address)).amount;
+    };
+    // 20000 down to 10000 quote base units per option base unit, long finished
+    const finished = new anchor.BN(2 * 10 ** 10);
+    await listAuction(finished, 10 ** 10, now - 1000, { linear: {} });
+    assert(await buyOne(finished) === BigInt(10000), "finished auction not at its end price");
+    assert((await program.account.listing.fetch(findListing(finished).listing)).amount.toNumber() === 9, "auction not partially filled");
+    try {
+      await listAuction(finished, 10 ** 10, now, { linear: {} });
+      assert(false, "restarted a running auction");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "InvalidAuction");
+    }
+    // 40000 down to 10000 exponentially, 60 seconds in it is near 17400
+    const running = new anchor.BN(4 * 10 ** 10);
+    await listAuction(running, 10 ** 10, now - 60, { exponential: {} });
+    const cost = await buyOne(running);
+    assert(cost > BigInt(15000) && cost < BigInt(20000), "auction price not decayed exponentially");
+    const later = await buyOne(running);
+    assert(later <= cost, "auction price rose");
+    // exponential auctions can't fall past the fixed point range of the decay
+    try {
+      await listAuction(new anchor.BN(2 * 10 ** 13), 1, now, { exponential: {} });
+      assert(false, "listed an exponential auction with an unpriceable end");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "InvalidAuction");
+    }
+  });
+  // opens a pool on a fresh put series at `basePrice` quote per contract. model priced pools read `oracle`,
+  // by default also the settlement feed of their cash settled series
+  const openPool = async (basePrice: anchor.BN, amount: number, dateChange?: number, curve: object = { constantProduct: {} }, oracle: PublicKey = PublicKey.default, seriesOracle: PublicKey = oracle) => {
+    const series = seriesOracle.equals(PublicKey.default)
+      ? await createOption(false, true, dateChange)
+      : await writeOption({ call: false, resellable: true, date: Math.floor(Date.now() / 1000 + (dateChange ?? 1000000)), underlying: await mintToken(), quote: await mintToken(), oracle: seriesOracle });
+    const { optionDataAccount, optionMint, userOptionTokenAccount, quoteMint } = series;
+    const [pool] = PublicKey.findProgramAddressSync(
+      [Buffer.from("pool"), optionMint.toBuffer(), basePrice.toArrayLike(Buffer, "be", 8)],
+      program.programId,
+    );
+    const [poolOptionVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("pool_option_vault"), pool.toBuffer()],
+      program.programId,
+    );
+    const [poolQuoteVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("pool_quote_vault"), pool.toBuffer()],
+      program.programId,
+    );
+    const [lpMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lp_mint"), pool.toBuffer()],
+      program.programId,
+    );
+    const userLpTokenAccount = getAssociatedTokenAddressSync(lpMint, wallet.publicKey);
+    await program.methods.createPool(basePrice, new anchor.BN(amount), curve as any).accounts({
+      signer: wallet.publicKey,
+      userOptionAccount: userOptionTokenAccount,
+      optionMint,
+      optionDataAccount,
+      quoteMint,
+      pool,
+      oracle,
+      poolQuoteVault,
+      poolOptionVault,
+      treasuryVault: findTreasuryVault(quoteMint),
+      lpMint,
+      userLpTokenAccount,
+      programAuthority,
+    }).rpc();
+    // unbounded slippage and no deadline unless given
+    const swap = (amount: number, buy: boolean, maxQuoteIn = U64_MAX, minQuoteOut = new anchor.BN(0), deadline: anchor.BN | null = null) => program.methods.swapPool(basePrice, new anchor.BN(amount), buy, maxQuoteIn, minQuoteOut, deadline).accounts({
+      signer: wallet.publicKey,
+      userOptionTokenAccount,
+      optionMint,
+      optionDataAccount,
+      pool,
+      oracle,
+      poolOptionVault,
+      poolQuoteVault,
+      userQuoteTokenAccount: series.userQuoteTokenAccount,
+      treasuryVault: findTreasuryVault(quoteMint),
+      programAuthority,
+    }).rpc();
+    const swapExactQuote = (quoteAmount: number, buy: boolean, optionLimit: number, deadline: anchor.BN | null = null) => program.methods.swapPoolExactQuote(basePrice, new anchor.BN(quoteAmount), buy, new anchor.BN(optionLimit), deadline).accounts({
+      signer: wallet.publicKey,
+      userOptionTokenAccount,
+      optionMint,
+      optionDataAccount,
+      pool,
+      oracle,
+      poolOptionVault,
+      poolQuoteVault,
+      userQuoteTokenAccount: series.userQuoteTokenAccount,
+      treasuryVault: findTreasuryVault(quoteMint),
+      programAuthority,
+    }).rpc();
+    return { ...series, poolOptionVault, poolQuoteVault, pool, lpMint, userLpTokenAccount, swap, swapExactQuote };
+  };
+  it("prices pool swaps along a constant product curve", async () => {
+    // opens at 2 quote per contract with 100 contracts of depth
+    const { pool, quoteMint, userQuoteTokenAccount, swap } = await openPool(new anchor.BN(2 * 10 ** OPTION_DECIMALS), 100 * 10 ** OPTION_DECIMALS);
+    const quoteBalance = async () => (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    const contracts = 10 * 10 ** OPTION_DECIMALS;
+    let before = await quoteBalance();
+    await swap(contracts, true);
+    const firstCost = before - await quoteBalance();
+    // 200 quote of depth over 90 contracts left, rounded up, plus the 1 basis point fee
+    assert(firstCost === BigInt(22222223 + 2223), "first buy mispriced");
+    before = await quoteBalance();
+    await swap(contracts, true);
+    const secondCost = before - await quoteBalance();
+    assert(secondCost > firstCost, "price did not rise with the trade");
+    before = await quoteBalance();
+    await swap(contracts, false);
+    const proceeds = await quoteBalance() - before;
+    assert(proceeds < secondCost, "sold back above the buy price");
+    const poolData = await program.account.pool.fetch(pool);
+    assert(poolData.optionReserve.toNumber() === 90 * 10 ** OPTION_DECIMALS);
+    // everything paid in stays in the pool except the protocol's half of the fees
+    const protocolFees = (await getAccount(provider.connection, findTreasuryVault(quoteMint))).amount;
+    assert(protocolFees > BigInt(0), "protocol fees not collected");
+    assert(BigInt(poolData.quoteReserve.toNumber()) === firstCost + secondCost - proceeds - protocolFees);
+  });
+  it("rejects pool swaps past their slippage limit or deadline", async () => {
+    const { swap } = await openPool(new anchor.BN(2 * 10 ** OPTION_DECIMALS), 100 * 10 ** OPTION_DECIMALS);
+    const contracts = 10 * 10 ** OPTION_DECIMALS;
+    const expectError = async (attempt: Promise<string>, code: string) => {
+      try {
+        await attempt;
+        assert(false, `expected ${code}`);
+      } catch (e) {
+        assert(e instanceof anchor.AnchorError, "unexpected error");
+        assert(e.error.errorCode.code === code);
+      }
+    };
+    // the first 10 contracts cost 22224446 with fees
+    await expectError(swap(contracts, true, new anchor.BN(22224445)), "SlippageExceeded");
+    await expectError(swap(contracts, true, U64_MAX, new anchor.BN(0), new anchor.BN(Math.floor(Date.now() / 1000) - 100)), "DeadlineExceeded");
+    await swap(contracts, true, new anchor.BN(22224446), new anchor.BN(0), new anchor.BN(Math.floor(Date.now() / 1000) + 100));
+    await expectError(swap(contracts, false, U64_MAX, U64_MAX), "SlippageExceeded");
+  });
+  it("swaps exact quote amounts against a pool", async () => {
+    const { userQuoteTokenAccount, userOptionTokenAccount, swapExactQuote } = await openPool(new anchor.BN(2 * 10 ** OPTION_DECIMALS), 100 * 10 ** OPTION_DECIMALS);
+    const quoteBalance = async () => (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    const optionBalance = async () => (await getAccount(provider.connection, userOptionTokenAccount)).amount;
+    // 22224446 buys exactly 10 contracts, see the constant product test
+    let quoteBefore = await quoteBalance();
+    let optionsBefore = await optionBalance();
+    await swapExactQuote(22224446, true, 10 * 10 ** OPTION_DECIMALS);
+    assert(quoteBefore - await quoteBalance() === BigInt(22224446), "exact input not spent");
+    assert(await optionBalance() - optionsBefore === BigInt(10 * 10 ** OPTION_DECIMALS), "wrong options out");
+    // selling for exactly 5 quote delivers as few options as needed
+    quoteBefore = await quoteBalance();
+    optionsBefore = await optionBalance();
+    await swapExactQuote(5 * 10 ** OPTION_DECIMALS, false, 10 * 10 ** OPTION_DECIMALS);
+    assert(await quoteBalance() - quoteBefore === BigInt(5 * 10 ** OPTION_DECIMALS), "exact output not received");
+    const sold = optionsBefore - await optionBalance();
+    assert(sold > BigInt(2 * 10 ** OPTION_DECIMALS) && sold < BigInt(3 * 10 ** OPTION_DECIMALS), "wrong options in");
+    // limits on the option side are enforced
+    try {
+      await swapExactQuote(5 * 10 ** OPTION_DECIMALS, false, 10 ** OPTION_DECIMALS);
+      assert(false, "sold past the option limit");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "SlippageExceeded");
+    }
+  });
+  it("prices model pools off the oracle spot with an inventory skew", async () => {
+    // spot at the 200 quote put strike, one underlying token per contract
+    const mockOracle = Keypair.generate();
+    await program.methods.initMockOracle(new anchor.BN(200), new anchor.BN(0), 0).accounts({
+      signer: wallet.publicKey,
+      treasury,
+      mockOracle: mockOracle.publicKey,
+    }).signers([mockOracle]).rpc();
+    // volatility is capped so the model stays within its fixed point range
+    try {
+      const extreme = { blackScholes: { volatilityBps: new anchor.BN(100001), skewBps: new anchor.BN(1000) } };
+      await openPool(new anchor.BN(2 * 10 ** OPTION_DECIMALS), 100 * 10 ** OPTION_DECIMALS, 1000000, extreme, mockOracle.publicKey);
+      assert(false, "opened a pool above the volatility cap");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "InvalidPoolCurve");
+    }
+    const curve = { blackScholes: { volatilityBps: new anchor.BN(8000), skewBps: new anchor.BN(1000) } };
+    // the pool must read the series' own feed, and physically settled series have none
+    const otherOracle = Keypair.generate();
+    await program.methods.initMockOracle(new anchor.BN(200), new anchor.BN(0), 0).accounts({
+      signer: wallet.publicKey,
+      treasury,
+      mockOracle: otherOracle.publicKey,
+    }).signers([otherOracle]).rpc();
+    for (const [seriesOracle, code] of [[otherOracle.publicKey, "InvalidAccount"], [PublicKey.default, "InvalidPoolCurve"]] as const) {
+      try {
+        await openPool(new anchor.BN(2 * 10 ** OPTION_DECIMALS), 100 * 10 ** OPTION_DECIMALS, 1000000, curve, mockOracle.publicKey, seriesOracle);
+        assert(false, "opened a model pool on an unrelated feed");
+      } catch (e) {
+        assert(e instanceof anchor.AnchorError, "unexpected error");
+        assert(e.error.errorCode.code === code);
+      }
+    }
+    const { userQuoteTokenAccount, swap } = await openPool(new anchor.BN(2 * 10 ** OPTION_DECIMALS), 100 * 10 ** OPTION_DECIMALS, 1000000, curve, mockOracle.publicKey);
+    const quoteBalance = async () => (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    const contract = 10 ** OPTION_DECIMALS;
+    let before = await quoteBalance();
+    await swap(contract, true);
+    const firstCost = before - await quoteBalance();
+    // an at the money put with ~11.6 days left at 80% volatility is worth ~11.4 quote
+    assert(firstCost > BigInt(11 * 10 ** OPTION_DECIMALS) && firstCost < BigInt(12 * 10 ** OPTION_DECIMALS), "model price off");
+    before = await quoteBalance();
+    await swap(contract, true);
+    const secondCost = before - await quoteBalance();
+    assert(secondCost > firstCost, "inventory skew not applied");
+  });
+  it("reads a time weighted pool price from swap observations", async () => {
+    const basePrice = new anchor.BN(2 * 10 ** OPTION_DECIMALS);
+    const { pool, optionMint, swap } = await openPool(basePrice, 100 * 10 ** OPTION_DECIMALS);
+    const twap = (window: number) => program.methods.poolTwap(basePrice, new anchor.BN(window)).accounts({ optionMint, pool }).view();
+    const marginalPrice = async () => {
+      const data = await program.account.pool.fetch(pool);
+      return data.quoteReserve.add(data.virtualQuoteReserve).muln(10 ** OPTION_DECIMALS).div(data.optionReserve).toNumber();
+    };
+    try {
+      await twap(0);
+      assert(false, "read a twap before any swap");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "InsufficientPriceHistory");
+    }
+    await swap(10 * 10 ** OPTION_DECIMALS, true);
+    const firstPrice = await marginalPrice();
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+    await swap(10 * 10 ** OPTION_DECIMALS, true);
+    const secondPrice = await marginalPrice();
+    assert(secondPrice > firstPrice, "price did not rise");
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+    assert((await twap(0)).toNumber() === secondPrice, "spot read is not the marginal price");
+    // the second swap lands within the snapshot interval, so both windows run from the first swap
+    for (const window of [1, 4]) {
+      const average = (await twap(window)).toNumber();
+      assert(average > firstPrice && average < secondPrice, "twap outside the observed prices");
+    }
+    const data = await program.account.pool.fetch(pool);
+    assert(data.price.toNumber() === secondPrice, "latest price not exposed");
+    assert(data.priceCumulative.gtn(0) && data.priceTimestamp.gtn(0), "price cumulative not exposed");
+    try {
+      await twap(3600);
+      assert(false, "read a twap older than the pool");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "InsufficientPriceHistory");
+    }
+  });
+  it("adds and removes pool liquidity by share", async () => {
+    const basePrice = new anchor.BN(2 * 10 ** OPTION_DECIMALS);
+    const opened = await openPool(basePrice, 100 * 10 ** OPTION_DECIMALS);
+    const { pool, lpMint, userLpTokenAccount, optionMint, optionDataAccount, userOptionTokenAccount, userQuoteTokenAccount, poolOptionVault, poolQuoteVault, swap } = opened;
+    assert((await getAccount(provider.connection, userLpTokenAccount)).amount === BigInt(100 * 10 ** OPTION_DECIMALS), "opening shares not minted");
+    await swap(50 * 10 ** OPTION_DECIMALS, true);
+    const liquidityAccounts = {
+      signer: wallet.publicKey,
+      optionMint,
+      optionDataAccount,
+      pool,
+      lpMint,
+      userLpTokenAccount,
+      userOptionTokenAccount,
+      userQuoteTokenAccount,
+      poolOptionVault,
+      poolQuoteVault,
+      programAuthority,
+    };
+    // half the option reserve mints half the supply, with quote in proportion
+    const before = await program.account.pool.fetch(pool);
+    await program.methods.addLiquidity(basePrice, new anchor.BN(25 * 10 ** OPTION_DECIMALS)).accounts(liquidityAccounts).rpc();
+    const after = await program.account.pool.fetch(pool);
+    assert((await getAccount(provider.connection, userLpTokenAccount)).amount === BigInt(150 * 10 ** OPTION_DECIMALS), "shares not pro-rata");
+    assert(after.quoteReserve.toNumber() === Math.ceil(before.quoteReserve.toNumber() * 1.5), "quote not deposited in proportion");
+    // burning every share empties the pool
+    await program.methods.removeLiquidity(basePrice, new anchor.BN(150 * 10 ** OPTION_DECIMALS)).accounts(liquidityAccounts).rpc();
+    const emptied = await program.account.pool.fetch(pool);
+    assert(emptied.optionReserve.toNumber() === 0);
+    assert(emptied.quoteReserve.toNumber() === 0);
+  });
+  it("returns pool reserves to the owner on close", async () => {
+    const basePrice = new anchor.BN(2 * 10 ** OPTION_DECIMALS);
+    const opened = await openPool(basePrice, 100 * 10 ** OPTION_DECIMALS, 4);
+    const { pool, lpMint, userLpTokenAccount, optionMint, optionDataAccount, userOptionTokenAccount, userQuoteTokenAccount, poolOptionVault, poolQuoteVault, swap, swapExactQuote } = opened;
+    await swap(10 * 10 ** OPTION_DECIMALS, true);
+    const poolData = await program.account.pool.fetch(pool);
+    assert(poolData.owner.equals(wallet.publicKey), "owner not recorded");
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+    // expired options can't be sold into the pool or deposited with it
+    const expired = [
+      () => swap(10 * 10 ** OPTION_DECIMALS, false),
+      () => swapExactQuote(10 ** OPTION_DECIMALS, false, 10 * 10 ** OPTION_DECIMALS),
+      () => program.methods.addLiquidity(basePrice, new anchor.BN(10 * 10 ** OPTION_DECIMALS)).accounts({
+        signer: wallet.publicKey,
+        optionMint,
+        optionDataAccount,
+        pool,
+        lpMint,
+        userLpTokenAccount,
+        userOptionTokenAccount,
+        userQuoteTokenAccount,
+        poolOptionVault,
+        poolQuoteVault,
+        programAuthority,
+      }).rpc(),
+    ];
+    for (const attempt of expired) {
+      try {
+        await attempt();
+        assert(false, "traded with an expired pool");
+      } catch (e) {
+        assert(e instanceof anchor.AnchorError, "unexpected error");
+        assert(e.error.errorCode.code === "OptionExpired");
+      }
+    }
+    const optionBefore = (await getAccount(provider.connection, userOptionTokenAccount)).amount;
+    const quoteBefore = (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    await program.methods.closePool(basePrice).accounts({
+      signer: wallet.publicKey,
+      optionMint,
+      optionDataAccount,
+      pool,
+      lpMint,
+      userLpTokenAccount,
+      poolOptionVault,
+      poolQuoteVault,
+      userOptionTokenAccount,
+      userQuoteTokenAccount,
+      programAuthority,
+    }).rpc();
+    const optionAfter = (await getAccount(provider.connection, userOptionTokenAccount)).amount;
+    const quoteAfter = (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    assert(optionAfter === optionBefore + BigInt(poolData.optionReserve.toNumber()), "options not returned");
+    assert(quoteAfter === quoteBefore + BigInt(poolData.quoteReserve.toNumber()), "quote not returned");
+    assert((await getAccount(provider.connection, userLpTokenAccount)).amount === BigInt(0), "lp shares not burned");
+    assert(await provider.connection.getAccountInfo(pool) === null, "pool not closed");
+    assert(await provider.connection.getAccountInfo(poolOptionVault) === null, "pool vault not closed");
   });
   it("exercises successfully", async () => {
     let { optionDataAccount, underlyingMint, userUnderlyingTokenAccount, 
-      underlyingTokenAccount, optionMint, userOptionTokenAccount } = await createOption(false, false);
+      underlyingTokenAccount, optionMint, userOptionTokenAccount,
+      quoteTokenAccount, userQuoteTokenAccount, writerPosition } = await createOption(false, false);
     
     let optionDataBefore = await program.account.optionDataAccount.fetch(optionDataAccount);
+    let optionBalanceBefore = (await getAccount(provider.connection, userOptionTokenAccount, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+    let quoteBalanceBefore = (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
     await program.methods.exercise(new anchor.BN(10)).accounts({
       signer: wallet.publicKey,
       optionMint,
+      optionTokenProgram: TOKEN_2022_PROGRAM_ID,
       optionDataAccount,
       underlyingTokenAccount,
+      quoteTokenAccount,
       userOptionTokenAccount,
-      creator: wallet.publicKey,
-      creatorTokenAccount: userUnderlyingTokenAccount,
       userUnderlyingTokenAccount,
+      userQuoteTokenAccount,
+      oracle: PublicKey.default,
       programAuthority,
-    }).rpc();
+    }).remainingAccounts(positionMetas([writerPosition])).rpc();
     let optionDataAfter = await program.account.optionDataAccount.fetch(optionDataAccount);
     assert(optionDataBefore.amountUnexercised.toNumber() === optionDataAfter.amountUnexercised.toNumber() + 10);
+    let optionBalanceAfter = (await getAccount(provider.connection, userOptionTokenAccount, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+    assert(optionBalanceBefore === optionBalanceAfter + BigInt(10), "option tokens not burned");
+    let quoteBalanceAfter = (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    assert(quoteBalanceAfter === quoteBalanceBefore + BigInt(10 * 200), "strike not paid in quote");
+    let position = await program.account.writerPosition.fetch(writerPosition);
+    assert(position.contractsAssigned.toNumber() === 10);
+    assert(position.proceedsClaimable.toNumber() === 10, "delivered underlying not credited to writer");
     {
       let { optionDataAccount, underlyingMint, userUnderlyingTokenAccount, 
-        underlyingTokenAccount, optionMint, userOptionTokenAccount } = await createOption(true, false);
+        underlyingTokenAccount, optionMint, userOptionTokenAccount,
+        quoteTokenAccount, userQuoteTokenAccount, writerPosition } = await createOption(true, false);
       
       let optionDataBefore = await program.account.optionDataAccount.fetch(optionDataAccount);
       await program.methods.exercise(new anchor.BN(10)).accounts({
         signer: wallet.publicKey,
         optionMint,
+        optionTokenProgram: TOKEN_2022_PROGRAM_ID,
         optionDataAccount,
         underlyingTokenAccount,
+        quoteTokenAccount,
         userOptionTokenAccount,
-        creator: wallet.publicKey,
-        creatorTokenAccount: userUnderlyingTokenAccount,
         userUnderlyingTokenAccount,
+        userQuoteTokenAccount,
+        oracle: PublicKey.default,
         programAuthority,
-      }).rpc();
+      }).remainingAccounts(positionMetas([writerPosition])).rpc();
       let optionDataAfter = await program.account.optionDataAccount.fetch(optionDataAccount);
       assert(optionDataBefore.amountUnexercised.toNumber() === optionDataAfter.amountUnexercised.toNumber() + 10);
     }
 
   });
+  it("assigns exercises round-robin across writers", async () => {
+    const underlying = await mintToken();
+    const quote = await mintToken();
+    const date = Math.floor(Date.now() / 1000 + 1000000);
+    const writer = await fundedWriter(underlying.mint, quote.mint);
+    const lastWriter = await fundedWriter(underlying.mint, quote.mint);
+    const first = await writeOption({ call: true, resellable: false, date, underlying, quote, amount: 100 });
+    const second = await writeOption({ call: true, resellable: false, date, underlying: writer.underlying, quote: writer.quote, amount: 100, writer: writer.keypair });
+    const third = await writeOption({ call: true, resellable: false, date, underlying: lastWriter.underlying, quote: lastWriter.quote, amount: 100, writer: lastWriter.keypair });
+    // the second writer buys back everything they wrote, leaving an empty position in the rotation
+    await program.methods.closePosition(new anchor.BN(100)).accounts({
+      signer: writer.keypair.publicKey,
+      optionMint: second.optionMint,
+      optionTokenProgram: TOKEN_2022_PROGRAM_ID,
+      userOptionTokenAccount: second.userOptionTokenAccount,
+      optionDataAccount: second.optionDataAccount,
+      writerPosition: second.writerPosition,
+      underlyingTokenAccount: second.underlyingTokenAccount,
+      quoteTokenAccount: second.quoteTokenAccount,
+      userUnderlyingTokenAccount: writer.underlying.tokenAccount,
+      userQuoteTokenAccount: writer.quote.tokenAccount,
+      programAuthority,
+    }).signers([writer.keypair]).rpc();
+    const exercise = (amount: number, positions: PublicKey[]) => program.methods.exercise(new anchor.BN(amount)).accounts({
+      signer: wallet.publicKey,
+      optionMint: first.optionMint,
+      optionTokenProgram: TOKEN_2022_PROGRAM_ID,
+      optionDataAccount: first.optionDataAccount,
+      underlyingTokenAccount: first.underlyingTokenAccount,
+      quoteTokenAccount: first.quoteTokenAccount,
+      userOptionTokenAccount: first.userOptionTokenAccount,
+      userUnderlyingTokenAccount: underlying.tokenAccount,
+      userQuoteTokenAccount: quote.tokenAccount,
+      oracle: PublicKey.default,
+      programAuthority,
+    }).remainingAccounts(positionMetas(positions)).rpc();
+    const skipping = async (amount: number, positions: PublicKey[]) => {
+      try {
+        await exercise(amount, positions);
+        assert(false, "skipped a position in the rotation");
+      } catch (e) {
+        assert(e instanceof anchor.AnchorError, "unexpected error");
+        assert(e.error.errorCode.code === "InvalidAccount");
+      }
+    };
+    await exercise(60, [first.writerPosition]);
+    // every position from the cursor on must be passed, empty ones are assigned nothing
+    await skipping(30, [third.writerPosition]);
+    await exercise(30, [second.writerPosition, third.writerPosition]);
+    // back at the first writer, who still has open contracts and can't be passed over
+    await skipping(10, [third.writerPosition]);
+    const firstPosition = await program.account.writerPosition.fetch(first.writerPosition);
+    const secondPosition = await program.account.writerPosition.fetch(second.writerPosition);
+    const thirdPosition = await program.account.writerPosition.fetch(third.writerPosition);
+    assert(firstPosition.contractsAssigned.toNumber() === 60);
+    assert(firstPosition.proceedsClaimable.toNumber() === 60 * 200);
+    assert(secondPosition.contractsAssigned.toNumber() === 0);
+    assert(thirdPosition.contractsAssigned.toNumber() === 30);
+    assert(thirdPosition.proceedsClaimable.toNumber() === 30 * 200);
+    const optionData = await program.account.optionDataAccount.fetch(first.optionDataAccount);
+    assert(optionData.assignmentCursor.toNumber() === 0);
+  });
+  it("closes a position by burning options before expiry", async () => {
+    const { optionDataAccount, optionMint, userOptionTokenAccount, writerPosition,
+      underlyingTokenAccount, quoteTokenAccount, userUnderlyingTokenAccount, userQuoteTokenAccount } = await createOption(false, false);
+    const quoteBefore = (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    await program.methods.closePosition(new anchor.BN(100)).accounts({
+      signer: wallet.publicKey,
+      optionMint,
+      optionTokenProgram: TOKEN_2022_PROGRAM_ID,
+      userOptionTokenAccount,
+      optionDataAccount,
+      writerPosition,
+      underlyingTokenAccount,
+      quoteTokenAccount,
+      userUnderlyingTokenAccount,
+      userQuoteTokenAccount,
+      programAuthority,
+    }).rpc();
+    const quoteAfter = (await getAccount(provider.connection, userQuoteTokenAccount)).amount;
+    assert(quoteAfter === quoteBefore + BigInt(100 * 200), "put collateral not returned");
+    const optionData = await program.account.optionDataAccount.fetch(optionDataAccount);
+    assert(optionData.amountUnexercised.toNumber() === 400 * 10 ** OPTION_DECIMALS - 100);
+    const position = await program.account.writerPosition.fetch(writerPosition);
+    assert(position.contractsWritten.toNumber() === 400 * 10 ** OPTION_DECIMALS - 100);
+    const optionBalance = (await getAccount(provider.connection, userOptionTokenAccount, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+    assert(optionBalance === BigInt(400 * 10 ** OPTION_DECIMALS - 100), "options not burned");
+  });
+  it("cash settles exercises against the oracle", async () => {
+    const mockOracle = Keypair.generate();
+    await program.methods.initMockOracle(new anchor.BN(250), new anchor.BN(0), 0).accounts({
+      signer: wallet.publicKey,
+      treasury,
+      mockOracle: mockOracle.publicKey,
+    }).signers([mockOracle]).rpc();
+    const underlying = await mintToken();
+    const quote = await mintToken();
+    const date = Math.floor(Date.now() / 1000 + 1000000);
+    const series = await writeOption({ call: false, resellable: false, date, underlying, quote, amount: 10 * 10 ** OPTION_DECIMALS, oracle: mockOracle.publicKey });
+    const exercise = () => program.methods.exercise(new anchor.BN(10 ** OPTION_DECIMALS)).accounts({
+      signer: wallet.publicKey,
+      optionMint: series.optionMint,
+      optionTokenProgram: TOKEN_2022_PROGRAM_ID,
+      optionDataAccount: series.optionDataAccount,
+      underlyingTokenAccount: series.underlyingTokenAccount,
+      quoteTokenAccount: series.quoteTokenAccount,
+      userOptionTokenAccount: series.userOptionTokenAccount,
+      userUnderlyingTokenAccount: underlying.tokenAccount,
+      userQuoteTokenAccount: quote.tokenAccount,
+      oracle: mockOracle.publicKey,
+      programAuthority,
+    }).remainingAccounts(positionMetas([series.writerPosition])).rpc();
+    // spot above the put strike, nothing to settle
+    try {
+      await exercise();
+      assert(false, "exercised out of the money");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "StrikePriceNotReached");
+    }
+    // a confidence interval wider than 2% of the price is rejected
+    await program.methods.setMockPrice(new anchor.BN(150), new anchor.BN(10)).accounts({
+      signer: wallet.publicKey,
+      mockOracle: mockOracle.publicKey,
+    }).rpc();
+    try {
+      await exercise();
+      assert(false, "exercised against an uncertain price");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "PriceConfidenceTooWide");
+    }
+    await program.methods.setMockPrice(new anchor.BN(150), new anchor.BN(1)).accounts({
+      signer: wallet.publicKey,
+      mockOracle: mockOracle.publicKey,
+    }).rpc();
+    const underlyingBefore = (await getAccount(provider.connection, underlying.tokenAccount)).amount;
+    const quoteBefore = (await getAccount(provider.connection, quote.tokenAccount)).amount;
+    await exercise();
+    const underlyingAfter = (await getAccount(provider.connection, underlying.tokenAccount)).amount;
+    const quoteAfter = (await getAccount(provider.connection, quote.tokenAccount)).amount;
+    assert(underlyingAfter === underlyingBefore, "cash settlement moved underlying");
+    assert(quoteAfter === quoteBefore + BigInt(50 * 10 ** OPTION_DECIMALS), "holder not paid intrinsic value");
+    const position = await program.account.writerPosition.fetch(series.writerPosition);
+    assert(position.proceedsClaimable.toNumber() === 150 * 10 ** OPTION_DECIMALS, "writer not credited remaining collateral");
+    // calls are collateralized in underlying and pay the full intrinsic value in it, uncapped by the strike
+    await program.methods.setMockPrice(new anchor.BN(500), new anchor.BN(1)).accounts({
+      signer: wallet.publicKey,
+      mockOracle: mockOracle.publicKey,
+    }).rpc();
+    const call = await writeOption({ call: true, resellable: false, date, underlying, quote, amount: 10 * 10 ** OPTION_DECIMALS, oracle: mockOracle.publicKey });
+    const callUnderlyingBefore = (await getAccount(provider.connection, underlying.tokenAccount)).amount;
+    await program.methods.exercise(new anchor.BN(10 ** OPTION_DECIMALS)).accounts({
+      signer: wallet.publicKey,
+      optionMint: call.optionMint,
+      optionTokenProgram: TOKEN_2022_PROGRAM_ID,
+      optionDataAccount: call.optionDataAccount,
+      underlyingTokenAccount: call.underlyingTokenAccount,
+      quoteTokenAccount: call.quoteTokenAccount,
+      userOptionTokenAccount: call.userOptionTokenAccount,
+      userUnderlyingTokenAccount: underlying.tokenAccount,
+      userQuoteTokenAccount: quote.tokenAccount,
+      oracle: mockOracle.publicKey,
+      programAuthority,
+    }).remainingAccounts(positionMetas([call.writerPosition])).rpc();
+    // 300 of intrinsic value at a spot of 500 is 3/5 of the contract's underlying
+    const callUnderlyingAfter = (await getAccount(provider.connection, underlying.tokenAccount)).amount;
+    assert(callUnderlyingAfter === callUnderlyingBefore + BigInt(6 * 10 ** 5), "holder not paid intrinsic value in underlying");
+    const callPosition = await program.account.writerPosition.fetch(call.writerPosition);
+    assert(callPosition.proceedsClaimable.toNumber() === 4 * 10 ** 5, "writer not credited remaining underlying");
+  });
+  it("enforces european and bermudan exercise windows", async () => {
+    const underlying = await mintToken();
+    const quote = await mintToken();
+    const now = Math.floor(Date.now() / 1000);
+    const exercise = (series: Awaited<ReturnType<typeof writeOption>>) => program.methods.exercise(new anchor.BN(10)).accounts({
+      signer: wallet.publicKey,
+      optionMint: series.optionMint,
+      optionTokenProgram: TOKEN_2022_PROGRAM_ID,
+      optionDataAccount: series.optionDataAccount,
+      underlyingTokenAccount: series.underlyingTokenAccount,
+      quoteTokenAccount: series.quoteTokenAccount,
+      userOptionTokenAccount: series.userOptionTokenAccount,
+      userUnderlyingTokenAccount: underlying.tokenAccount,
+      userQuoteTokenAccount: quote.tokenAccount,
+      oracle: PublicKey.default,
+      programAuthority,
+    }).remainingAccounts(positionMetas([series.writerPosition])).rpc();
+    // european: only in the hour before expiry
+    const european = await writeOption({ call: true, resellable: false, date: now + 1000000, underlying, quote, exerciseStyle: { european: {} } });
+    try {
+      await exercise(european);
+      assert(false, "exercised european option early");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "OutsideExerciseWindow");
+    }
+    // bermudan: open in the hour before the first date
+    const dates = [new anchor.BN(now + 60), new anchor.BN(now + 500000)];
+    const bermudan = await writeOption({ call: true, resellable: false, date: now + 1000000, underlying, quote, exerciseStyle: { bermudan: { dates } } });
+    await exercise(bermudan);
+    const optionData = await program.account.optionDataAccount.fetch(bermudan.optionDataAccount);
+    assert(optionData.amountUnexercised.toNumber() === 400 * 10 ** OPTION_DECIMALS - 10);
+    assert(!bermudan.optionMint.equals(european.optionMint), "exercise styles share a series");
+  });
+  it("fails to exercise without option tokens", async () => {
+    const { optionDataAccount, userUnderlyingTokenAccount, underlyingTokenAccount, optionMint,
+      quoteMint, quoteTokenAccount, userQuoteTokenAccount, writerPosition } = await createOption(false, false);
+    const account = Keypair.generate();
+    await provider.connection.requestAirdrop(account.publicKey, LAMPORTS_PER_SOL);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+    const accountOptionHolder = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      wallet.payer,
+      optionMint,
+      account.publicKey,
+      false,
+      undefined,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const accountUnderlyingHolder = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      wallet.payer,
+      (await program.account.optionDataAccount.fetch(optionDataAccount)).underlyingMint,
+      account.publicKey
+    );
+    const accountQuoteHolder = await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      wallet.payer,
+      quoteMint,
+      account.publicKey
+    );
+    try {
+      await program.methods.exercise(new anchor.BN(10)).accounts({
+        signer: account.publicKey,
+        optionMint,
+        optionTokenProgram: TOKEN_2022_PROGRAM_ID,
+        optionDataAccount,
+        underlyingTokenAccount,
+        quoteTokenAccount,
+        userOptionTokenAccount: accountOptionHolder.address,
+        userUnderlyingTokenAccount: accountUnderlyingHolder.address,
+        userQuoteTokenAccount: accountQuoteHolder.address,
+        oracle: PublicKey.default,
+        programAuthority,
+      }).remainingAccounts(positionMetas([writerPosition])).signers([account]).rpc();
+      assert(false, "exercised without option tokens");
+    } catch (e) {
+      assert(e instanceof anchor.AnchorError, "unexpected error");
+      assert(e.error.errorCode.code === "NotEnoughOptionToken");
+    }
+  });
   it("claims successfully after expiry", async () => {
     {
       let { optionDataAccount, underlyingMint, userUnderlyingTokenAccount, 
-        underlyingTokenAccount, optionMint, userOptionTokenAccount } = await createOption(false, false, 1);
-        await new Promise((resolve) => setTimeout(resolve, 2000));
+        underlyingTokenAccount, optionMint, userOptionTokenAccount,
+        quoteMint, quoteTokenAccount, userQuoteTokenAccount, writerPosition } = await createOption(false, false, 1);
+        await new Promise((resolve) => setTimeout(resolve, 3000));
       await program.methods.claim().accounts({
         signer: wallet.publicKey,
         optionMint,
         optionDataAccount,
+        writerPosition,
         underlyingMint,
-        programHolderAccount: underlyingTokenAccount,
+        quoteMint,
+        underlyingTokenAccount,
         userUnderlyingAccount: userUnderlyingTokenAccount,
+        quoteTokenAccount,
+        userQuoteAccount: userQuoteTokenAccount,
         programAuthority,
       }).rpc();
     }
     {
       let { optionDataAccount, underlyingMint, userUnderlyingTokenAccount, 
-        underlyingTokenAccount, optionMint, userOptionTokenAccount } = await createOption(true, false, 1);
-        await new Promise((resolve) => setTimeout(resolve, 2000));
+        underlyingTokenAccount, optionMint, userOptionTokenAccount,
+        quoteMint, quoteTokenAccount, userQuoteTokenAccount, writerPosition } = await createOption(true, false, 1);
+        await new Promise((resolve) => setTimeout(resolve, 3000));
         await program.methods.claim().accounts({
           signer: wallet.publicKey,
           optionMint,
           optionDataAccount,
+          writerPosition,
           underlyingMint,
-          programHolderAccount: underlyingTokenAccount,
+          quoteMint,
+          underlyingTokenAccount,
           userUnderlyingAccount: userUnderlyingTokenAccount,
+          quoteTokenAccount,
+          userQuoteAccount: userQuoteTokenAccount,
           programAuthority,
         }).rpc();
     }
   });
+  it("claims each writer's own share of a multi-writer series", async () => {
+    const underlying = await mintToken();
+    const quote = await mintToken();
+    const date = Math.floor(Date.now() / 1000 + 5);
+    const writer = await fundedWriter(underlying.mint, quote.mint);
+    const first = await writeOption({ call: true, resellable: false, date, underlying, quote, amount: 300 });
+    const second = await writeOption({ call: true, resellable: false, date, underlying: writer.underlying, quote: writer.quote, amount: 100, writer: writer.keypair });
+    const position = await program.account.writerPosition.fetch(second.writerPosition);
+    assert(position.writer.equals(writer.keypair.publicKey));
+    assert(position.contractsWritten.toNumber() === 100);
+    assert(position.collateralDeposited.toNumber() === 100);
+    await new Promise((resolve) => setTimeout(resolve, 6000));
+    for (const [w, signers] of [[first, []], [second, [writer.keypair]]] as const) {
+      const before = (await getAccount(provider.connection, w.userUnderlyingTokenAccount)).amount;
+      await program.methods.claim().accounts({
+        signer: w.signer,
+        optionMint: w.optionMint,
+        optionDataAccount: w.optionDataAccount,
+        writerPosition: w.writerPosition,
+        underlyingMint: underlying.mint,
+        quoteMint: quote.mint,
+        underlyingTokenAccount: w.underlyingTokenAccount,
+        userUnderlyingAccount: w.userUnderlyingTokenAccount,
+        quoteTokenAccount: w.quoteTokenAccount,
+        userQuoteAccount: w.userQuoteTokenAccount,
+        programAuthority,
+      }).signers([...signers]).rpc();
+      const after = (await getAccount(provider.connection, w.userUnderlyingTokenAccount)).amount;
+      const written = w === first ? 300 : 100;
+      assert(after === before + BigInt(written), "writer did not get their own collateral back");
+    }
+  });
+  it("writes puts quoted in wrapped SOL", async () => {
+    const userWrappedSolAccount = await createWrappedNativeAccount(
+      provider.connection,
+      wallet.payer,
+      wallet.publicKey,
+      100 * LAMPORTS_PER_SOL,
+    );
+    const { optionDataAccount, quoteTokenAccount } = await createOption(false, false, 1000000, { mint: NATIVE_MINT, tokenAccount: userWrappedSolAccount });
+    const optionData = await program.account.optionDataAccount.fetch(optionDataAccount);
+    assert(optionData.quoteMint.equals(NATIVE_MINT));
+    const vault = await getAccount(provider.connection, quoteTokenAccount);
+    assert(vault.mint.equals(NATIVE_MINT));
+    assert(vault.amount === BigInt(200 * 400 * 10 ** OPTION_DECIMALS), "wrapped SOL collateral not deposited");
+  });
 });